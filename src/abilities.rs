@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+
+use crate::behavior::Intent;
+use crate::dice::DiceExpr;
+use crate::effects::{Condition, StatusEffect};
+use crate::player::Player;
+
+/// What an ability does when it is used.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AbilityKind {
+    /// Attack the target and every enemy within `radius` of it.
+    Area { radius: f32, damage: DiceExpr },
+    /// Restore armor to the most hurt ally in range, the user included.
+    Heal { amount: DiceExpr },
+    /// Rush up to `range` at the target and strike it with `damage`.
+    Charge { damage: DiceExpr },
+    /// Raise the user's defense by `amount` for `turns` turns.
+    Shield { amount: DiceExpr, turns: i32 },
+    /// Attack the target `shots` times with the user's usual damage.
+    Volley { shots: u32 },
+}
+
+/// An ability declared in the simulation YAML's `abilities:` catalog.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "AbilityFields")]
+pub struct Ability {
+    #[serde(flatten)]
+    pub kind: AbilityKind,
+    /// Turns the user has to wait before it can use the ability again.
+    pub cooldown: i32,
+    /// How far away the ability reaches, the user's attack range when left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<i32>,
+    /// Inflicted on every player the ability hits.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<StatusEffect>,
+}
+
+/// The `kind:` an ability's other fields are read by.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum KindTag {
+    Area,
+    Heal,
+    Charge,
+    Shield,
+    Volley,
+}
+
+/// An ability the way the YAML spells it out, the fields of every kind side by side.
+/// Reading it through a flattened `AbilityKind` would swallow keys nobody knows.
+#[derive(Deserialize)]
+struct AbilityFields {
+    kind: KindTag,
+    radius: Option<f32>,
+    damage: Option<DiceExpr>,
+    amount: Option<DiceExpr>,
+    turns: Option<i32>,
+    shots: Option<u32>,
+    cooldown: i32,
+    range: Option<i32>,
+    #[serde(default)]
+    effects: Vec<StatusEffect>,
+}
+
+impl TryFrom<AbilityFields> for Ability {
+    type Error = String;
+
+    fn try_from(fields: AbilityFields) -> Result<Self, Self::Error> {
+        let AbilityFields { kind, radius, damage, amount, turns, shots, cooldown, range, effects } = fields;
+        let (name, wanted): (&str, &[&str]) = match kind {
+            KindTag::Area => ("an area", &["radius", "damage"]),
+            KindTag::Heal => ("a heal", &["amount"]),
+            KindTag::Charge => ("a charge", &["damage"]),
+            KindTag::Shield => ("a shield", &["amount", "turns"]),
+            KindTag::Volley => ("a volley", &["shots"]),
+        };
+        let given = [
+            ("radius", radius.is_some()),
+            ("damage", damage.is_some()),
+            ("amount", amount.is_some()),
+            ("turns", turns.is_some()),
+            ("shots", shots.is_some()),
+        ];
+        if let Some((field, _)) = given.iter().find(|(field, set)| *set && !wanted.contains(field)) {
+            return Err(format!("{} ability has no {}", name, field));
+        }
+        let missing = |field: &str| format!("{} ability needs {}", name, field);
+        let kind = match kind {
+            KindTag::Area => AbilityKind::Area {
+                radius: radius.ok_or_else(|| missing("radius"))?,
+                damage: damage.ok_or_else(|| missing("damage"))?,
+            },
+            KindTag::Heal => AbilityKind::Heal { amount: amount.ok_or_else(|| missing("amount"))? },
+            KindTag::Charge => AbilityKind::Charge { damage: damage.ok_or_else(|| missing("damage"))? },
+            KindTag::Shield => AbilityKind::Shield {
+                amount: amount.ok_or_else(|| missing("amount"))?,
+                turns: turns.ok_or_else(|| missing("turns"))?,
+            },
+            KindTag::Volley => AbilityKind::Volley { shots: shots.ok_or_else(|| missing("shots"))? },
+        };
+        Ok(Ability { kind, cooldown, range, effects })
+    }
+}
+
+pub type AbilityCatalog = BTreeMap<String, Ability>;
+
+/// An ability a player knows, with the turns left until it is ready again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AbilitySlot {
+    pub name: String,
+    pub ability: Ability,
+    pub cooldown: i32,
+}
+
+impl AbilitySlot {
+    pub fn is_ready(&self) -> bool {
+        self.cooldown <= 0
+    }
+}
+
+impl Ability {
+    pub fn reach(&self, user: &Player) -> f32 {
+        self.range.unwrap_or_else(|| user.effective_range()) as f32
+    }
+    fn is_offensive(&self) -> bool {
+        matches!(
+            self.kind,
+            AbilityKind::Area { .. } | AbilityKind::Charge { .. } | AbilityKind::Volley { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownAbility {
+    pub name: String,
+}
+
+impl fmt::Display for UnknownAbility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no ability named '{}' in the abilities section", self.name)
+    }
+}
+
+impl std::error::Error for UnknownAbility {}
+
+/// Look up the abilities a player config names.
+pub fn learn(catalog: &AbilityCatalog, names: &[String]) -> Result<Vec<AbilitySlot>, UnknownAbility> {
+    names
+        .iter()
+        .map(|name| {
+            let ability = catalog.get(name).ok_or_else(|| UnknownAbility { name: name.clone() })?;
+            Ok(AbilitySlot {
+                name: name.clone(),
+                ability: ability.clone(),
+                cooldown: 0,
+            })
+        })
+        .collect()
+}
+
+/// The ally in reach of `healer` who is missing the most armor, if anyone is below half.
+pub fn heal_target(healer: &Player, reach: f32, players: &VecDeque<Player>) -> Option<usize> {
+    players
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| healer.is_ally(p) && healer.loc.distance(&p.loc) <= reach)
+        .filter(|(_, p)| p.armor.curr * 2 <= p.armor.base)
+        .max_by_key(|(_, p)| p.armor.base - p.armor.curr)
+        .map(|(idx, _)| idx)
+}
+
+/// The action-selection step: picks the first ready ability, in the order the player
+/// lists them, that is worth using this turn instead of acting on `intent`.
+/// Offensive abilities only replace attacking or closing in.
+pub fn choose(me: &Player, intent: Intent, target: &Player, players: &VecDeque<Player>) -> Option<usize> {
+    let distance = me.loc.distance(&target.loc);
+    let wants_fight = matches!(intent, Intent::Attack | Intent::Advance);
+    me.abilities.iter().position(|slot| {
+        let ability = &slot.ability;
+        let reach = ability.reach(me);
+        if !slot.is_ready() || (ability.is_offensive() && !wants_fight) {
+            return false;
+        }
+        match &ability.kind {
+            AbilityKind::Area { radius, .. } => {
+                let hit = players
+                    .iter()
+                    .filter(|p| !me.is_ally(p) && p.loc.distance(&target.loc) <= *radius)
+                    .count();
+                distance <= reach && hit >= 2
+            }
+            AbilityKind::Heal { .. } => {
+                // the healer itself is not in `players` while it acts
+                me.armor.curr * 2 <= me.armor.base || heal_target(me, reach, players).is_some()
+            }
+            AbilityKind::Charge { .. } => intent == Intent::Advance && distance <= reach,
+            AbilityKind::Shield { .. } => {
+                let threat = (target.effective_range() + target.speed.curr) as f32;
+                !me.has_condition(Condition::Shielded) && distance <= threat
+            }
+            AbilityKind::Volley { .. } => intent == Intent::Attack && distance <= reach,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CATALOG: &str = "
+cleave:
+  kind: area
+  radius: 3
+  damage: 1d6
+  cooldown: 3
+  range: 2
+mend:
+  kind: heal
+  amount: 2d4
+  cooldown: 2
+  range: 6
+rush:
+  kind: charge
+  damage: 1d10
+  cooldown: 4
+  range: 20
+";
+
+    #[test]
+    fn test_learn_and_choose() {
+        let catalog: AbilityCatalog = serde_yaml::from_str(CATALOG).unwrap();
+        let names = ["mend", "cleave", "rush"].map(String::from);
+        let mut me = Player::fighter("me", 0.0);
+        me.abilities = learn(&catalog, &names).unwrap();
+        assert!(learn(&catalog, &["fireball".to_string()]).is_err());
+
+        let mut players = VecDeque::new();
+        players.push_back(Player::fighter("a", 2.0));
+        players.push_back(Player::fighter("b", 3.0));
+        assert_eq!(choose(&me, Intent::Attack, &players[0], &players), Some(1));
+        me.armor.curr = 4;
+        assert_eq!(choose(&me, Intent::Attack, &players[0], &players), Some(0));
+        me.abilities[0].cooldown = 1;
+        me.abilities[1].cooldown = 1;
+        assert_eq!(choose(&me, Intent::Attack, &players[0], &players), None);
+
+        let far = Player::fighter("far", 15.0);
+        assert_eq!(choose(&me, Intent::Advance, &far, &players), Some(2));
+        assert_eq!(choose(&me, Intent::Flee, &far, &players), None);
+    }
+}
@@ -3,9 +3,13 @@
 // Prompts: "Can you integrate a TUI using the ratatui crate for rust?"
 // AI Source URL: https://www.anthropic.com/claude/sonnet
 
+use std::cell::RefCell;
 use std::collections::VecDeque;
-use crate::game::Game;
+use std::rc::Rc;
+use crate::events::{EventLog, GameEvent};
+use crate::game::{BattleStatus, Game, TurnOutcome};
 use crate::player::Player;
+use crate::replay::Playback;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum AppState {
@@ -31,33 +35,80 @@ pub enum BattleEventType {
     Miss,
     Death,
     Info,
+    Critical,
+    Fumble,
+    Effect,
+    Ability,
+    Heal,
+    Morale,
+}
+
+impl From<&GameEvent> for BattleEventType {
+    fn from(event: &GameEvent) -> Self {
+        match event {
+            GameEvent::Moved { .. } => BattleEventType::Movement,
+            GameEvent::AttackRolled { critical: true, .. } => BattleEventType::Critical,
+            GameEvent::AttackRolled { fumble: true, .. } => BattleEventType::Fumble,
+            GameEvent::AttackRolled { .. } if event.is_hit() => BattleEventType::Attack,
+            GameEvent::AttackRolled { .. } | GameEvent::AttackBlocked { .. } => BattleEventType::Miss,
+            GameEvent::Damaged { .. } | GameEvent::Fell { .. } => BattleEventType::Hit,
+            GameEvent::Defeated { .. } => BattleEventType::Death,
+            GameEvent::AbilityUsed { .. } | GameEvent::ItemUsed { .. } => BattleEventType::Ability,
+            GameEvent::Healed { .. } => BattleEventType::Heal,
+            GameEvent::EffectApplied { .. }
+            | GameEvent::EffectTicked { .. }
+            | GameEvent::EffectExpired { .. }
+            | GameEvent::Stunned { .. } => BattleEventType::Effect,
+            GameEvent::MoraleChecked { .. }
+            | GameEvent::Routed { .. }
+            | GameEvent::Surrendered { .. }
+            | GameEvent::Escaped { .. } => BattleEventType::Morale,
+            GameEvent::TurnStarted { .. }
+            | GameEvent::Held { .. }
+            | GameEvent::RoundStarted { .. }
+            | GameEvent::TurnLimitReached { .. } => BattleEventType::Info,
+        }
+    }
 }
 
 pub struct App {
     pub state: AppState,
     pub game: Game,
     pub battle_log: VecDeque<BattleEvent>,
-    pub current_turn: i32,
     pub auto_advance: bool,
     pub tick_rate: u64, // milliseconds
     pub max_log_entries: usize,
+    pub seed: Option<u64>,
+    /// When set the battle is played back from a recording instead of rolled.
+    pub playback: Option<Playback>,
+    pub teams: Vec<String>,
+    events: Rc<RefCell<EventLog>>,
 }
 
 impl App {
     pub fn new() -> Self {
+        let events = Rc::new(RefCell::new(EventLog::default()));
+        let mut game = Game::new();
+        game.subscribe(Box::new(events.clone()));
         Self {
             state: AppState::Setup,
-            game: Game::new(),
+            game,
             battle_log: VecDeque::new(),
-            current_turn: 0,
             auto_advance: false,
             tick_rate: 500, // 500ms between auto-advances
             max_log_entries: 50,
+            seed: None,
+            playback: None,
+            teams: Vec::new(),
+            events,
         }
     }
 
     pub fn add_players(&mut self, players: Vec<Player>) {
         for player in players {
+            if !self.teams.iter().any(|team| team == player.side()) {
+                self.teams.push(player.side().to_string());
+            }
             self.game.players.push_back(player);
         }
     }
@@ -96,90 +147,31 @@ impl App {
         self.state = AppState::Quit;
     }
 
-    pub fn step_battle(&mut self, rng: &mut rand::rngs::ThreadRng) -> bool {
-        if self.game.players.len() <= 1 {
-            self.finish_battle();
-            return false;
-        }
-
-        if self.current_turn >= crate::MAX_TURNS {
+    pub fn step_battle<R: rand::Rng>(&mut self, rng: &mut R) -> bool {
+        if let Some(playback) = self.playback.as_mut() {
+            playback.step(&mut self.game);
+        } else if let TurnOutcome::Idle { player } = self.game.step(rng) {
             self.add_battle_event(
-                format!("Battle reached maximum turns: {}", crate::MAX_TURNS),
+                format!("{} has nobody to fight", player),
                 BattleEventType::Info,
             );
-            self.finish_battle();
-            return false;
         }
 
-        // Execute one turn of the battle
-        if let Some(mut player) = self.game.players.pop_front() {
-            let player_name = player.name.clone();
-            
-            // Get target information without borrowing self
-            let target_info = if let Some((target_idx, nearest_player)) = self.game.get_nearest(&player) {
-                Some((target_idx, nearest_player.name.clone(), nearest_player.loc.clone()))
-            } else {
-                None
-            };
-            
-            if let Some((target_idx, target_name, target_loc)) = target_info {
-                if player.in_range(&target_loc) {
-                    self.add_battle_event(
-                        format!("{} is in range of {}", player_name, target_name),
-                        BattleEventType::Info,
-                    );
-                    
-                    // Get mutable reference to target for combat
-                    let combat_result = if let Some((_, nearest_player)) = self.game.get_nearest(&player) {
-                        if player.attack(nearest_player, rng) {
-                            let damage_done = player.damage(nearest_player, rng);
-                            let target_is_dead = nearest_player.is_dead();
-                            Some((true, damage_done, target_is_dead))
-                        } else {
-                            Some((false, 0, false))
-                        }
-                    } else {
-                        None
-                    };
-                    
-                    if let Some((hit, damage_done, target_is_dead)) = combat_result {
-                        if hit {
-                            self.add_battle_event(
-                                format!("{} hit {} for {} damage", player_name, target_name, damage_done),
-                                BattleEventType::Hit,
-                            );
-                            
-                            if target_is_dead {
-                                self.add_battle_event(
-                                    format!("{} defeated {}", player_name, target_name),
-                                    BattleEventType::Death,
-                                );
-                                self.game.players.remove(target_idx);
-                            }
-                        } else {
-                            self.add_battle_event(
-                                format!("{} missed", player_name),
-                                BattleEventType::Miss,
-                            );
-                        }
-                    }
-                } else {
-                    let distance = player.loc.distance(&target_loc);
-                    self.add_battle_event(
-                        format!("{} moves towards {} (distance: {:.1})", player_name, target_name, distance),
-                        BattleEventType::Movement,
-                    );
-                    player.move_towards(&target_loc);
-                }
+        let events = self.events.borrow_mut().drain();
+        for (turn, event) in events {
+            if matches!(event, GameEvent::TurnStarted { .. }) {
+                continue;
             }
-            self.game.players.push_back(player);
+            self.push_battle_event(BattleEvent {
+                turn,
+                message: event.to_string(),
+                event_type: BattleEventType::from(&event),
+            });
         }
 
-        self.current_turn += 1;
-        self.game.turns = self.current_turn;
-
         // Check if battle is over
-        if self.game.players.len() <= 1 {
+        let replay_done = self.playback.as_ref().is_some_and(|p| p.is_finished());
+        if replay_done || self.game.status() != BattleStatus::Ongoing {
             self.finish_battle();
             return false;
         }
@@ -189,13 +181,11 @@ impl App {
 
     pub fn finish_battle(&mut self) {
         self.state = AppState::Finished;
-        if self.game.players.len() == 1 {
-            let winner = &self.game.players[0];
-            self.add_battle_event(
-                format!("{} is the winner with {}/{} health remaining!", 
-                       winner.name, winner.armor.curr, winner.armor.base),
-                BattleEventType::Info,
-            );
+        if let Some(winner) = self.game.winning_side() {
+            let (curr, base) = self.game.remaining_armor();
+            let message = format!("{} is the winner with {}/{} health remaining!",
+                                  winner, curr, base);
+            self.add_battle_event(message, BattleEventType::Info);
         } else {
             self.add_battle_event(
                 "Battle ended inconclusively".to_string(),
@@ -205,12 +195,14 @@ impl App {
     }
 
     pub fn add_battle_event(&mut self, message: String, event_type: BattleEventType) {
-        let event = BattleEvent {
-            turn: self.current_turn,
+        self.push_battle_event(BattleEvent {
+            turn: self.game.turns,
             message,
             event_type,
-        };
-        
+        });
+    }
+
+    fn push_battle_event(&mut self, event: BattleEvent) {
         self.battle_log.push_back(event);
         
         // Keep only the most recent entries
@@ -219,12 +211,13 @@ impl App {
         }
     }
 
-    pub fn get_winner(&self) -> Option<&Player> {
-        if self.game.players.len() == 1 {
-            self.game.players.front()
-        } else {
-            None
-        }
+    pub fn get_winner(&self) -> Option<&str> {
+        self.game.winning_side()
+    }
+
+    /// Position of the player's team in the original roster, used to pick its colors.
+    pub fn team_index(&self, player: &Player) -> usize {
+        self.teams.iter().position(|team| team == player.side()).unwrap_or(0)
     }
 
     pub fn get_battle_log(&self) -> &VecDeque<BattleEvent> {
@@ -0,0 +1,538 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::player::Location;
+
+/// Length of the steps movement is checked against the terrain in.
+const STEP: f32 = 0.5;
+/// Defense a target gains from every unit in the way of an attack.
+const UNIT_COVER: i32 = 2;
+/// Slack for the rounding that builds up when a move is walked step by step.
+const EPSILON: f32 = 1e-4;
+/// How far past its footprint an enemy holds up anybody moving by.
+const ZONE_OF_CONTROL: f32 = 1.0;
+/// Movement spent per unit of distance for every step taken inside an enemy's zone of control.
+const ZONE_COST: f32 = 2.0;
+/// How many times a move that runs into a unit is halved to find where the two touch.
+const CONTACT_SEARCH: usize = 16;
+
+/// An axis-aligned rectangle of the arena floor.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Region {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Region {
+    pub fn contains(&self, loc: &Location) -> bool {
+        loc.x >= self.x && loc.x <= self.x + self.width && loc.y >= self.y && loc.y <= self.y + self.height
+    }
+}
+
+fn two() -> i32 {
+    2
+}
+
+/// Ground that makes anybody standing in it harder to hit.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "CoverFields")]
+pub struct Cover {
+    #[serde(flatten)]
+    pub region: Region,
+    pub defense: i32,
+}
+
+/// Raised ground, such as a ledge or a hill, standing `level` above the arena floor.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "ElevationFields")]
+pub struct Elevation {
+    #[serde(flatten)]
+    pub region: Region,
+    pub level: f32,
+}
+
+// Cover and elevation are read field by field rather than through the flattened region,
+// which would swallow keys nobody knows.
+
+#[derive(Deserialize)]
+struct CoverFields {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    #[serde(default = "two")]
+    defense: i32,
+}
+
+impl From<CoverFields> for Cover {
+    fn from(fields: CoverFields) -> Self {
+        let CoverFields { x, y, width, height, defense } = fields;
+        Cover { region: Region { x, y, width, height }, defense }
+    }
+}
+
+#[derive(Deserialize)]
+struct ElevationFields {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    level: f32,
+}
+
+impl From<ElevationFields> for Elevation {
+    fn from(fields: ElevationFields) -> Self {
+        let ElevationFields { x, y, width, height, level } = fields;
+        Elevation { region: Region { x, y, width, height }, level }
+    }
+}
+
+/// The room a unit takes up on the field, a ball of radius `size` around `loc`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Footprint {
+    pub loc: Location,
+    pub size: f32,
+    /// Whether the unit is an enemy of whoever is moving past it.
+    pub hostile: bool,
+}
+
+impl Footprint {
+    /// Whether a unit of `size` standing at `loc` would overlap this one.
+    pub fn overlaps(&self, loc: &Location, size: f32) -> bool {
+        self.loc.distance(loc) < self.size + size - EPSILON
+    }
+    /// Whether a unit of `size` at `loc` is close enough to be held up by this one.
+    pub fn controls(&self, loc: &Location, size: f32) -> bool {
+        self.hostile && self.loc.distance(loc) < self.size + size + ZONE_OF_CONTROL
+    }
+}
+
+/// A unit on the move: how much room it takes up, whether it flies and who else is about.
+#[derive(Debug, Clone, Copy)]
+pub struct Mover<'a> {
+    pub size: f32,
+    pub flying: bool,
+    pub crowd: &'a [Footprint],
+}
+
+impl Mover<'_> {
+    /// Whether stepping from `at` to `next` runs into a unit it is closing in on. Units
+    /// already overlapping can always move apart.
+    pub fn bumps(&self, at: &Location, next: &Location) -> bool {
+        self.crowd
+            .iter()
+            .any(|unit| unit.overlaps(next, self.size) && unit.loc.distance(next) < unit.loc.distance(at))
+    }
+    /// How much dearer moving on from `at` is for the enemies standing close by.
+    pub fn zone_cost(&self, at: &Location) -> f32 {
+        if self.crowd.iter().any(|unit| unit.controls(at, self.size)) {
+            ZONE_COST
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Where a move ended.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Travel {
+    pub end: Location,
+    /// How many of the waypoints the mover got to.
+    pub reached: usize,
+    /// The highest single drop a walker took on the way.
+    pub fall: f32,
+}
+
+/// What the arena floor is like at a spot, the strongest feature winning where they overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Terrain {
+    Open,
+    /// Costs twice the movement to cross.
+    Difficult,
+    Cover(i32),
+    /// Nobody can stand in or move through it.
+    Wall,
+    /// Beyond the arena's bounds.
+    Outside,
+}
+
+/// What lies between an attacker and its target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sight {
+    Clear,
+    /// Cover or other units in the way make the target this much harder to hit.
+    Partial(i32),
+    /// A wall is in the way, the target cannot be attacked at all.
+    Blocked,
+}
+
+impl Sight {
+    pub fn cover(&self) -> i32 {
+        match self {
+            Sight::Partial(cover) => *cover,
+            Sight::Clear | Sight::Blocked => 0,
+        }
+    }
+}
+
+/// The battlefield, set from the `arena:` section of the simulation YAML. It spans
+/// from the origin to `width` by `height`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Arena {
+    pub width: f32,
+    pub height: f32,
+    pub walls: Vec<Region>,
+    /// Terrain that halves the speed of anybody moving through it.
+    pub difficult: Vec<Region>,
+    pub cover: Vec<Cover>,
+    pub elevation: Vec<Elevation>,
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Arena::new(60.0, 60.0)
+    }
+}
+
+/// Why a player cannot start where its config puts it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Misplaced {
+    pub x: f32,
+    pub y: f32,
+    pub terrain: Terrain,
+}
+
+impl fmt::Display for Misplaced {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let place = match self.terrain {
+            Terrain::Wall => "inside a wall",
+            _ => "outside the arena",
+        };
+        write!(f, "starting location {}:{} is {}", self.x, self.y, place)
+    }
+}
+
+impl std::error::Error for Misplaced {}
+
+impl Arena {
+    /// An empty arena of the given size.
+    pub fn new(width: f32, height: f32) -> Self {
+        Arena {
+            width,
+            height,
+            walls: Vec::new(),
+            difficult: Vec::new(),
+            cover: Vec::new(),
+            elevation: Vec::new(),
+        }
+    }
+    pub fn in_bounds(&self, loc: &Location) -> bool {
+        loc.x >= 0.0 && loc.x <= self.width && loc.y >= 0.0 && loc.y <= self.height
+    }
+    /// Within a step of the arena's bounds, where a fleeing player can leave the field.
+    pub fn at_edge(&self, loc: &Location) -> bool {
+        loc.x <= STEP || loc.y <= STEP || loc.x >= self.width - STEP || loc.y >= self.height - STEP
+    }
+    pub fn terrain_at(&self, loc: &Location) -> Terrain {
+        if !self.in_bounds(loc) {
+            Terrain::Outside
+        } else if self.walls.iter().any(|wall| wall.contains(loc)) {
+            Terrain::Wall
+        } else if let Some(defense) = self.cover_at(loc) {
+            Terrain::Cover(defense)
+        } else if self.difficult.iter().any(|region| region.contains(loc)) {
+            Terrain::Difficult
+        } else {
+            Terrain::Open
+        }
+    }
+    /// How high the ground is at `loc`, the highest raised ground there winning.
+    pub fn ground_at(&self, loc: &Location) -> f32 {
+        self.elevation
+            .iter()
+            .filter(|raised| raised.region.contains(loc))
+            .map(|raised| raised.level)
+            .fold(0.0, f32::max)
+    }
+    pub fn is_blocked(&self, loc: &Location) -> bool {
+        matches!(self.terrain_at(loc), Terrain::Wall | Terrain::Outside)
+    }
+    /// Movement spent per unit of distance at `loc`.
+    pub fn movement_cost(&self, loc: &Location) -> f32 {
+        if self.difficult.iter().any(|region| region.contains(loc)) {
+            2.0
+        } else {
+            1.0
+        }
+    }
+    /// The defense bonus of the best cover at `loc`, if there is any.
+    pub fn cover_at(&self, loc: &Location) -> Option<i32> {
+        self.cover
+            .iter()
+            .filter(|cover| cover.region.contains(loc))
+            .map(|cover| cover.defense)
+            .max()
+    }
+    pub fn check(&self, loc: &Location) -> Result<(), Misplaced> {
+        match self.terrain_at(loc) {
+            terrain @ (Terrain::Wall | Terrain::Outside) => Err(Misplaced {
+                x: loc.x,
+                y: loc.y,
+                terrain,
+            }),
+            _ => Ok(()),
+        }
+    }
+    /// Points every step along the line from `from` to `to`, the ends included.
+    fn sight_line(from: &Location, to: &Location) -> impl Iterator<Item = Location> {
+        let steps = ((from.distance(to) / STEP).ceil() as usize).max(1);
+        let (from, to) = (from.clone(), to.clone());
+        (0..=steps).map(move |i| {
+            let t = i as f32 / steps as f32;
+            Location::new(from.x + (to.x - from.x) * t, from.y + (to.y - from.y) * t, from.z)
+        })
+    }
+    /// Whether a wall stands between `from` and `to`.
+    pub fn blocks_sight(&self, from: &Location, to: &Location) -> bool {
+        Self::sight_line(from, to).any(|point| self.walls.iter().any(|wall| wall.contains(&point)))
+    }
+    /// What an attack from `from` at `to` has to get past: walls block it, cover the line
+    /// crosses that the attacker is not standing in and the `units` in the way add to the
+    /// target's defense.
+    pub fn line_of_sight(&self, from: &Location, to: &Location, units: &[Footprint]) -> Sight {
+        if self.blocks_sight(from, to) {
+            return Sight::Blocked;
+        }
+        let terrain = self
+            .cover
+            .iter()
+            .filter(|cover| !cover.region.contains(from))
+            .filter(|cover| Self::sight_line(from, to).any(|point| cover.region.contains(&point)))
+            .map(|cover| cover.defense)
+            .max()
+            .unwrap_or(0);
+        let in_the_way = units
+            .iter()
+            .filter(|unit| Self::sight_line(from, to).any(|point| point.distance(&unit.loc) < unit.size))
+            .count() as i32;
+        match terrain + in_the_way * UNIT_COVER {
+            0 => Sight::Clear,
+            cover => Sight::Partial(cover),
+        }
+    }
+    /// Move from `from` in a straight line towards `to`, kept inside the bounds, spending
+    /// at most `budget` movement. Stops early at walls, up against other units and where
+    /// the budget runs out.
+    pub fn advance(&self, from: &Location, to: &Location, budget: f32, mover: &Mover) -> Travel {
+        let (end, _, fall) = self.walk(from, to, budget, mover);
+        let reached = usize::from(end == self.landing(to, mover.flying));
+        Travel { end, reached, fall }
+    }
+    /// Walk `route` waypoint by waypoint, spending at most `budget` movement.
+    pub fn follow(&self, from: &Location, route: &[Location], budget: f32, mover: &Mover) -> Travel {
+        let mut end = from.clone();
+        let mut left = budget;
+        let mut highest_fall = 0.0f32;
+        for (reached, waypoint) in route.iter().enumerate() {
+            let (next, spent, fall) = self.walk(&end, waypoint, left, mover);
+            left -= spent;
+            highest_fall = highest_fall.max(fall);
+            if next != self.landing(waypoint, mover.flying) {
+                return Travel {
+                    end: next,
+                    reached,
+                    fall: highest_fall,
+                };
+            }
+            end = next;
+        }
+        Travel {
+            end,
+            reached: route.len(),
+            fall: highest_fall,
+        }
+    }
+    /// Where a mover heading for `to` ends up standing there: inside the bounds, on the
+    /// ground when walking and no lower than it when flying.
+    pub fn landing(&self, to: &Location, flying: bool) -> Location {
+        let mut spot = Location::new(to.x.clamp(0.0, self.width), to.y.clamp(0.0, self.height), 0.0);
+        let ground = self.ground_at(&spot);
+        spot.z = if flying { to.z.max(ground) } else { ground };
+        spot
+    }
+    /// Where a straight move from `from` towards `to` ends, the movement it took and the
+    /// highest drop on the way.
+    fn walk(&self, from: &Location, to: &Location, budget: f32, mover: &Mover) -> (Location, f32, f32) {
+        let flying = mover.flying;
+        let to = self.landing(to, flying);
+        let length = if flying { from.distance(&to) } else { from.horizontal_distance(&to) };
+        if length == 0.0 || budget <= 0.0 {
+            return (from.clone(), 0.0, 0.0);
+        }
+        let steps = (length / STEP).ceil() as usize;
+        let between = |a: &Location, b: &Location, t: f32| {
+            let point = Location::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t, a.z + (b.z - a.z) * t);
+            self.landing(&point, flying)
+        };
+        let cost = |at: &Location, next: &Location| {
+            let distance = if flying {
+                at.distance(next)
+            } else {
+                // climbing costs the height gained on top of the distance
+                at.horizontal_distance(next) * self.movement_cost(next) + (next.z - at.z).max(0.0)
+            };
+            distance * mover.zone_cost(at)
+        };
+        let mut spent = 0.0;
+        let mut fall = 0.0f32;
+        let mut at = from.clone();
+        for i in 1..=steps {
+            let mut next = if i == steps { to.clone() } else { between(from, &to, i as f32 / steps as f32) };
+            if self.is_blocked(&next) {
+                break;
+            }
+            let bumped = mover.bumps(&at, &next);
+            if bumped {
+                // go as far as the two touching
+                let (mut clear, mut blocked) = (0.0, 1.0);
+                for _ in 0..CONTACT_SEARCH {
+                    let t = (clear + blocked) / 2.0;
+                    if mover.bumps(&at, &between(&at, &next, t)) {
+                        blocked = t;
+                    } else {
+                        clear = t;
+                    }
+                }
+                next = between(&at, &next, clear);
+            }
+            let step_cost = cost(&at, &next);
+            if spent + step_cost > budget + EPSILON {
+                let part = (budget - spent) / step_cost;
+                let partial = between(&at, &next, part);
+                if flying || partial.z <= at.z {
+                    fall = fall.max(at.z - partial.z);
+                    at = partial;
+                }
+                return (at, budget, if flying { 0.0 } else { fall });
+            }
+            spent += step_cost;
+            fall = fall.max(at.z - next.z);
+            at = next;
+            if bumped {
+                break;
+            }
+        }
+        (at, spent, if flying { 0.0 } else { fall })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARENA: &str = "
+width: 40
+height: 20
+walls:
+  - { x: 10, y: 0, width: 2, height: 15 }
+difficult:
+  - { x: 20, y: 0, width: 10, height: 20 }
+cover:
+  - { x: 0, y: 16, width: 5, height: 4, defense: 3 }
+  - { x: 3, y: 16, width: 5, height: 4 }
+";
+    const WALKER: Mover = Mover { size: 0.5, flying: false, crowd: &[] };
+    const FLYER: Mover = Mover { size: 0.5, flying: true, crowd: &[] };
+
+    #[test]
+    fn test_terrain_lookup() {
+        let arena: Arena = serde_yaml::from_str(ARENA).unwrap();
+
+        assert_eq!(arena.terrain_at(&Location::new(11.0, 5.0, 0.0)), Terrain::Wall);
+        assert_eq!(arena.terrain_at(&Location::new(25.0, 5.0, 0.0)), Terrain::Difficult);
+        assert_eq!(arena.terrain_at(&Location::new(4.0, 18.0, 0.0)), Terrain::Cover(3));
+        assert_eq!(arena.terrain_at(&Location::new(7.0, 18.0, 0.0)), Terrain::Cover(2));
+        assert_eq!(arena.terrain_at(&Location::new(41.0, 5.0, 0.0)), Terrain::Outside);
+        assert!(arena.check(&Location::new(5.0, 5.0, 0.0)).is_ok());
+        assert!(arena.check(&Location::new(10.5, 1.0, 0.0)).is_err());
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let arena: Arena = serde_yaml::from_str(ARENA).unwrap();
+        let at = |x, y| Location::new(x, y, 0.0);
+
+        assert_eq!(arena.line_of_sight(&at(5.0, 5.0), &at(15.0, 5.0), &[]), Sight::Blocked);
+        assert_eq!(arena.line_of_sight(&at(5.0, 17.0), &at(15.0, 17.0), &[]), Sight::Clear);
+        assert_eq!(arena.line_of_sight(&at(15.0, 17.0), &at(4.0, 18.0), &[]), Sight::Partial(3));
+        // cover the attacker stands in does not help the target
+        assert_eq!(arena.line_of_sight(&at(1.0, 18.0), &at(2.0, 18.0), &[]), Sight::Clear);
+        let unit = |x, y| Footprint { loc: at(x, y), size: 0.5, hostile: true };
+        let crowd = [unit(10.0, 17.0), unit(10.0, 19.0)];
+        assert_eq!(arena.line_of_sight(&at(5.0, 17.0), &at(15.0, 17.0), &crowd), Sight::Partial(2));
+    }
+
+    #[test]
+    fn test_movement_respects_terrain() {
+        let arena: Arena = serde_yaml::from_str(ARENA).unwrap();
+        let at = |x, y| Location::new(x, y, 0.0);
+        let walk = |from, to, budget| arena.advance(&from, &to, budget, &WALKER).end;
+
+        // open ground goes the full distance
+        assert_eq!(walk(at(0.0, 5.0), at(6.0, 5.0), 6.0), at(6.0, 5.0));
+        // walls stop the move in front of them
+        let blocked = walk(at(5.0, 5.0), at(15.0, 5.0), 10.0);
+        assert!(blocked.x < 10.0 && blocked.x > 9.0);
+        // difficult terrain halves the distance covered
+        let slowed = walk(at(20.0, 5.0), at(30.0, 5.0), 4.0);
+        assert!((slowed.x - 22.0).abs() < 0.01);
+        // the bounds keep everybody in
+        assert_eq!(walk(at(38.0, 5.0), at(45.0, 5.0), 7.0), at(40.0, 5.0));
+    }
+
+    #[test]
+    fn test_climbing_falling_and_flying() {
+        let mut arena = Arena::new(40.0, 20.0);
+        arena.elevation.push(Elevation {
+            region: Region { x: 10.0, y: 0.0, width: 10.0, height: 20.0 },
+            level: 5.0,
+        });
+        let at = |x, y, z| Location::new(x, y, z);
+
+        // climbing onto the ledge eats into the move
+        let climb = arena.advance(&at(5.0, 5.0, 0.0), &at(15.0, 5.0, 0.0), 10.0, &WALKER);
+        assert_eq!(climb.end.z, 5.0);
+        assert!(climb.end.x < 15.0 && climb.end.x >= 10.0);
+        // stepping off it is a fall
+        let drop = arena.advance(&at(19.0, 5.0, 5.0), &at(23.0, 5.0, 0.0), 4.0, &WALKER);
+        assert_eq!((drop.end, drop.fall), (at(23.0, 5.0, 0.0), 5.0));
+        // flyers go straight for the target's height and never fall
+        let flight = arena.advance(&at(0.0, 5.0, 8.0), &at(3.0, 5.0, 4.0), 10.0, &FLYER);
+        assert_eq!((flight.end, flight.fall), (at(3.0, 5.0, 4.0), 0.0));
+        assert_eq!(arena.landing(&at(15.0, 5.0, 1.0), true).z, 5.0);
+    }
+
+    #[test]
+    fn test_units_stop_against_each_other() {
+        let arena = Arena::new(40.0, 20.0);
+        let at = |x, y| Location::new(x, y, 0.0);
+        let unit = |x, hostile| Footprint { loc: at(x, 5.0), size: 0.5, hostile };
+
+        // walking into a unit stops right up against it
+        let friend = [unit(10.0, false)];
+        let mover = Mover { crowd: &friend, ..WALKER };
+        let stopped = arena.advance(&at(5.0, 5.0), &at(15.0, 5.0), 10.0, &mover).end;
+        assert!((stopped.distance(&at(10.0, 5.0)) - 1.0).abs() < 0.01);
+        // units on top of each other can still move apart
+        let apart = arena.advance(&at(10.0, 5.0), &at(5.0, 5.0), 3.0, &mover).end;
+        assert!((apart.x - 7.0).abs() < 0.01);
+        // moving on from next to an enemy costs double
+        let enemy = [unit(10.0, true)];
+        let mover = Mover { crowd: &enemy, ..WALKER };
+        let held_up = arena.advance(&at(9.0, 5.0), &at(0.0, 5.0), 1.0, &mover).end;
+        assert!((held_up.x - 8.5).abs() < 0.01);
+    }
+}
@@ -0,0 +1,195 @@
+use std::fmt;
+use std::thread;
+
+use crate::dice;
+use crate::game::Game;
+use crate::serialization::{self, SimulationConfig};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TeamStats {
+    /// The team, or the player's name for players fighting on their own.
+    pub name: String,
+    pub wins: u32,
+    /// Sum of the rounds taken by each of this team's victories.
+    pub turns_to_victory: u64,
+    /// Sum of the armor the survivors had left after each of this team's victories.
+    pub remaining_armor: i64,
+}
+
+impl TeamStats {
+    pub fn mean_turns_to_victory(&self) -> f64 {
+        if self.wins == 0 {
+            0.0
+        } else {
+            self.turns_to_victory as f64 / self.wins as f64
+        }
+    }
+    pub fn mean_remaining_armor(&self) -> f64 {
+        if self.wins == 0 {
+            0.0
+        } else {
+            self.remaining_armor as f64 / self.wins as f64
+        }
+    }
+}
+
+/// Aggregated results of running the same roster many times.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchStats {
+    pub runs: u32,
+    pub inconclusive: u32,
+    pub teams: Vec<TeamStats>,
+}
+
+impl BatchStats {
+    fn new(config: &SimulationConfig) -> Self {
+        let mut teams: Vec<TeamStats> = Vec::new();
+        for player in &config.players {
+            let Some(side) = player.team.as_ref().or(player.name.as_ref()) else {
+                continue;
+            };
+            if !teams.iter().any(|t| &t.name == side) {
+                teams.push(TeamStats {
+                    name: side.clone(),
+                    ..TeamStats::default()
+                });
+            }
+        }
+        BatchStats {
+            runs: 0,
+            inconclusive: 0,
+            teams,
+        }
+    }
+    fn record(&mut self, game: &Game) {
+        self.runs += 1;
+        match game.winning_side() {
+            Some(winner) => {
+                if let Some(stats) = self.teams.iter_mut().find(|t| t.name == winner) {
+                    stats.wins += 1;
+                    stats.turns_to_victory += game.turns as u64;
+                    stats.remaining_armor += game.remaining_armor().0 as i64;
+                }
+            }
+            None => self.inconclusive += 1,
+        }
+    }
+    fn merge(&mut self, other: BatchStats) {
+        self.runs += other.runs;
+        self.inconclusive += other.inconclusive;
+        for (mine, theirs) in self.teams.iter_mut().zip(other.teams) {
+            mine.wins += theirs.wins;
+            mine.turns_to_victory += theirs.turns_to_victory;
+            mine.remaining_armor += theirs.remaining_armor;
+        }
+    }
+    pub fn win_rate(&self, stats: &TeamStats) -> f64 {
+        if self.runs == 0 {
+            0.0
+        } else {
+            stats.wins as f64 / self.runs as f64
+        }
+    }
+    pub fn inconclusive_rate(&self) -> f64 {
+        if self.runs == 0 {
+            0.0
+        } else {
+            self.inconclusive as f64 / self.runs as f64
+        }
+    }
+}
+
+impl fmt::Display for BatchStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} runs", self.runs)?;
+        writeln!(
+            f,
+            "{:<20} {:>8} {:>12} {:>12}",
+            "team", "win rate", "mean rounds", "mean armor"
+        )?;
+        for stats in &self.teams {
+            writeln!(
+                f,
+                "{:<20} {:>7.1}% {:>12.1} {:>12.1}",
+                stats.name,
+                self.win_rate(stats) * 100.0,
+                stats.mean_turns_to_victory(),
+                stats.mean_remaining_armor()
+            )?;
+        }
+        write!(f, "inconclusive: {:.1}%", self.inconclusive_rate() * 100.0)
+    }
+}
+
+/// Play `runs` battles with the same roster, spread across `threads` worker threads.
+/// Anything the roster leaves out should be filled in beforehand, or every run rolls it anew.
+/// Run `n` is always seeded with `seed + n`, so the totals do not depend on the
+/// number of threads.
+pub fn run_batch(config: &SimulationConfig, runs: u32, threads: usize, seed: u64) -> BatchStats {
+    let threads = threads.clamp(1, runs.max(1) as usize) as u32;
+    let mut total = BatchStats::new(config);
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|worker| {
+                scope.spawn(move || {
+                    let mut stats = BatchStats::new(config);
+                    for run in (worker..runs).step_by(threads as usize) {
+                        let mut rng = dice::seeded_rng(seed.wrapping_add(run as u64));
+                        let mut game = Game::new();
+                        game.rules = config.rules.clone();
+                        game.arena = config.arena.clone();
+                        game.players.extend(serialization::players_from_config(config.clone(), &mut rng));
+                        game.run_simulation(&mut rng);
+                        stats.record(&game);
+                    }
+                    stats
+                })
+            })
+            .collect();
+        for worker in workers {
+            total.merge(worker.join().expect("batch worker panicked"));
+        }
+    });
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::{LocationConfig, PlayerConfig};
+
+    fn roster() -> SimulationConfig {
+        let player = |name: &str, x: f32| PlayerConfig {
+            name: Some(name.to_string()),
+            attack: Some(12),
+            defense: Some(12),
+            armor: Some(12),
+            power: Some(12),
+            speed: Some(12),
+            range: Some(3),
+            loc: Some(LocationConfig { x, y: 0.0, z: 0.0 }),
+            ..Default::default()
+        };
+        SimulationConfig {
+            version: None,
+            seed: None,
+            rules: Default::default(),
+            arena: Default::default(),
+            items: Default::default(),
+            abilities: Default::default(),
+            templates: Default::default(),
+            players: vec![player("A", 0.0), player("B", 30.0)],
+        }
+    }
+
+    #[test]
+    fn test_batch_is_independent_of_thread_count() {
+        let config = roster();
+        let single = run_batch(&config, 50, 1, 9);
+        let parallel = run_batch(&config, 50, 4, 9);
+
+        assert_eq!(single, parallel);
+        let wins: u32 = single.teams.iter().map(|p| p.wins).sum();
+        assert_eq!(wins + single.inconclusive, 50);
+    }
+}
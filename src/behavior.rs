@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+
+use crate::arena::Arena;
+use crate::player::Player;
+
+/// Players below this share of their armor start running when they are `fleeing`.
+const FLEE_THRESHOLD: f32 = 1.0 / 3.0;
+
+/// What a player wants to do about its target this turn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Intent {
+    Attack,
+    Advance,
+    Hold,
+    /// Back off by `distance` while staying in range.
+    Retreat { distance: f32 },
+    /// Run away from the target as fast as possible.
+    Flee,
+}
+
+/// Decides each turn between attacking, closing in, holding or backing off.
+pub trait Behavior {
+    fn decide(&self, me: &Player, target: &Player, arena: &Arena) -> Intent;
+}
+
+/// The behaviour profiles players can choose from in the simulation YAML.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BehaviorProfile {
+    #[default]
+    Aggressive,
+    Kiting,
+    Defensive,
+    Fleeing,
+    Support,
+}
+
+impl BehaviorProfile {
+    pub fn behavior(&self) -> &'static dyn Behavior {
+        match self {
+            BehaviorProfile::Aggressive => &Aggressive,
+            BehaviorProfile::Kiting => &Kiting,
+            BehaviorProfile::Defensive => &Defensive,
+            BehaviorProfile::Fleeing => &Fleeing,
+            BehaviorProfile::Support => &Support,
+        }
+    }
+    pub fn is_default(&self) -> bool {
+        *self == BehaviorProfile::default()
+    }
+}
+
+/// Attacks whatever is in range and in sight, otherwise closes in.
+pub struct Aggressive;
+
+impl Behavior for Aggressive {
+    fn decide(&self, me: &Player, target: &Player, arena: &Arena) -> Intent {
+        if me.in_range(target, arena) {
+            Intent::Attack
+        } else {
+            Intent::Advance
+        }
+    }
+}
+
+/// Uses a range advantage to back off to maximum range whenever the target could
+/// otherwise reach it next turn.
+pub struct Kiting;
+
+impl Behavior for Kiting {
+    fn decide(&self, me: &Player, target: &Player, arena: &Arena) -> Intent {
+        let distance = me.loc.distance(&target.loc);
+        let range = me.effective_range() as f32;
+        let their_reach = (target.effective_range() + target.speed.curr) as f32;
+        let retreat = (range - distance).min(me.speed.curr as f32);
+        // only worth giving up the attack if backing off gets us out of reach
+        if distance <= their_reach && retreat > 0.0 && distance + retreat > their_reach {
+            Intent::Retreat { distance: retreat }
+        } else {
+            Aggressive.decide(me, target, arena)
+        }
+    }
+}
+
+/// Holds its ground and only closes in on targets that are already near.
+pub struct Defensive;
+
+impl Behavior for Defensive {
+    fn decide(&self, me: &Player, target: &Player, arena: &Arena) -> Intent {
+        let distance = me.loc.distance(&target.loc);
+        if me.in_range(target, arena) {
+            Intent::Attack
+        } else if distance <= 2.0 * me.effective_range() as f32 {
+            Intent::Advance
+        } else {
+            Intent::Hold
+        }
+    }
+}
+
+/// Fights like `Aggressive` until badly hurt, then runs.
+pub struct Fleeing;
+
+impl Behavior for Fleeing {
+    fn decide(&self, me: &Player, target: &Player, arena: &Arena) -> Intent {
+        if (me.armor.curr as f32) < me.armor.base as f32 * FLEE_THRESHOLD {
+            Intent::Flee
+        } else {
+            Aggressive.decide(me, target, arena)
+        }
+    }
+}
+
+/// Stays just outside the enemy's reach so it can back up its allies, only attacking
+/// what comes within range.
+pub struct Support;
+
+impl Behavior for Support {
+    fn decide(&self, me: &Player, target: &Player, arena: &Arena) -> Intent {
+        let distance = me.loc.distance(&target.loc);
+        let their_reach = (target.effective_range() + target.speed.curr) as f32;
+        if me.in_range(target, arena) {
+            Intent::Attack
+        } else if distance - me.speed.curr as f32 > their_reach {
+            Intent::Advance
+        } else {
+            Intent::Hold
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(name: &str, x: f32, range: i32, speed: i32) -> Player {
+        let mut player = Player::fighter(name, x);
+        player.range.set(range);
+        player.speed.set(speed);
+        player
+    }
+
+    #[test]
+    fn test_kiting_backs_off_from_melee() {
+        let arena = Arena::default();
+        let archer = unit("archer", 0.0, 18, 8);
+        let brute = unit("brute", 10.0, 2, 10);
+
+        assert_eq!(Kiting.decide(&archer, &brute, &arena), Intent::Retreat { distance: 8.0 });
+        let far = unit("brute", 17.0, 2, 10);
+        assert_eq!(Kiting.decide(&archer, &far, &arena), Intent::Attack);
+        let faster = unit("rider", 10.0, 2, 20);
+        assert_eq!(Kiting.decide(&archer, &faster, &arena), Intent::Attack);
+        let close = unit("brute", 3.0, 2, 10);
+        assert_eq!(Kiting.decide(&archer, &close, &arena), Intent::Attack);
+    }
+
+    #[test]
+    fn test_fleeing_runs_when_hurt() {
+        let arena = Arena::default();
+        let mut coward = unit("coward", 0.0, 2, 10);
+        let brute = unit("brute", 1.0, 2, 10);
+
+        assert_eq!(Fleeing.decide(&coward, &brute, &arena), Intent::Attack);
+        coward.armor.curr = 3;
+        assert_eq!(Fleeing.decide(&coward, &brute, &arena), Intent::Flee);
+    }
+
+    #[test]
+    fn test_walls_keep_targets_out_of_range() {
+        let mut arena = Arena::default();
+        let archer = unit("archer", 1.0, 18, 8);
+        let brute = unit("brute", 11.0, 2, 10);
+        assert_eq!(Aggressive.decide(&archer, &brute, &arena), Intent::Attack);
+
+        arena.walls.push(crate::arena::Region { x: 5.0, y: 0.0, width: 1.0, height: 5.0 });
+        assert_eq!(Aggressive.decide(&archer, &brute, &arena), Intent::Advance);
+    }
+}
@@ -1,24 +1,395 @@
-use rand::{Rng, rngs::ThreadRng};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 use tracing::debug;
 
-pub fn roll3d6(rng: &mut ThreadRng) -> i32 {
-    let roll1 = rng.random_range(1..=6);
-    debug!("rolled {}/6", roll1);
-    let roll2 = rng.random_range(1..=6);
-    debug!("rolled {}/6", roll2);
-    let roll3 = rng.random_range(1..=6);
-    debug!("rolled {}/6", roll3);
-    roll1 + roll2 + roll3
+/// The generator every battle is played with. ChaCha8 produces the same stream on every
+/// platform, so a seed is enough to replay a battle.
+pub type BattleRng = ChaCha8Rng;
+
+pub fn seeded_rng(seed: u64) -> BattleRng {
+    BattleRng::seed_from_u64(seed)
 }
 
-pub fn roll1d20(rng: &mut ThreadRng) -> i32 {
-    let roll = rng.random_range(1..=20);
-    debug!("rolled {}/20", roll);
-    roll
+/// Exploding dice stop rerolling after this many extra dice.
+const MAX_EXPLOSIONS: usize = 100;
+/// Largest dice count, number of sides and constant a term may have, so that no roll can
+/// overflow or take forever.
+const MAX_DICE: i32 = 100;
+const MAX_SIDES: i32 = 1000;
+const MAX_CONSTANT: i32 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keep {
+    Highest(u32),
+    Lowest(u32),
+    /// Roll the dice twice and keep the better set, `1d20adv`.
+    Advantage,
+    /// Roll the dice twice and keep the worse set, `1d20dis`.
+    Disadvantage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Term {
+    Dice {
+        negative: bool,
+        count: u32,
+        sides: u32,
+        keep: Option<Keep>,
+        explode: bool,
+    },
+    Constant(i32),
+}
+
+/// A parsed dice expression such as `2d6+3`, `4d6kh3`, `1d20adv` or `3d8!`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DiceExpr {
+    pub terms: Vec<Term>,
 }
 
-pub fn roll1d8(rng: &mut ThreadRng) -> i32 {
-    let roll = rng.random_range(1..=8);
-    debug!("rolled {}/8", roll);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DieRoll {
+    pub sides: u32,
+    pub value: i32,
+    /// `false` for dice dropped by keep highest/lowest or advantage.
+    pub kept: bool,
+    /// `true` for the extra dice added by an exploding die.
+    pub exploded: bool,
+}
+
+/// The outcome of rolling a `DiceExpr`, with every die that was thrown.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Roll {
+    pub dice: Vec<DieRoll>,
+    pub modifier: i32,
+    pub total: i32,
+}
+
+impl Roll {
+    /// The value of the first kept die, what a `1d20` roll "shows".
+    pub fn natural(&self) -> i32 {
+        self.dice.iter().find(|d| d.kept).map_or(0, |d| d.value)
+    }
+}
+
+impl fmt::Display for Roll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dice: Vec<String> = self
+            .dice
+            .iter()
+            .map(|d| match (d.kept, d.exploded) {
+                (false, _) => format!("({})", d.value),
+                (true, true) => format!("{}!", d.value),
+                (true, false) => d.value.to_string(),
+            })
+            .collect();
+        write!(f, "[{}]", dice.join(" "))?;
+        if self.modifier != 0 {
+            write!(f, "{:+}", self.modifier)?;
+        }
+        write!(f, " = {}", self.total)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiceError {
+    pub expr: String,
+    pub message: String,
+}
+
+impl fmt::Display for DiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid dice expression '{}': {}", self.expr, self.message)
+    }
+}
+
+impl std::error::Error for DiceError {}
+
+fn roll_die<R: Rng + ?Sized>(sides: u32, rng: &mut R) -> i32 {
+    let roll = rng.random_range(1..=sides as i32);
+    debug!("rolled {}/{}", roll, sides);
     roll
 }
+
+fn roll_set<R: Rng + ?Sized>(count: u32, sides: u32, explode: bool, rng: &mut R) -> Vec<DieRoll> {
+    let mut dice = Vec::new();
+    for _ in 0..count {
+        let mut value = roll_die(sides, rng);
+        dice.push(DieRoll { sides, value, kept: true, exploded: false });
+        let mut explosions = 0;
+        while explode && value == sides as i32 && explosions < MAX_EXPLOSIONS {
+            value = roll_die(sides, rng);
+            dice.push(DieRoll { sides, value, kept: true, exploded: true });
+            explosions += 1;
+        }
+    }
+    dice
+}
+
+fn drop_all_but(dice: &mut [DieRoll], keep: u32, highest: bool) {
+    let mut order: Vec<usize> = (0..dice.len()).collect();
+    order.sort_by_key(|&i| if highest { -dice[i].value } else { dice[i].value });
+    for &i in order.iter().skip(keep as usize) {
+        dice[i].kept = false;
+    }
+}
+
+fn kept_sum(dice: &[DieRoll]) -> i32 {
+    dice.iter().filter(|d| d.kept).map(|d| d.value).sum()
+}
+
+impl DiceExpr {
+    pub fn parse(text: &str) -> Result<Self, DiceError> {
+        let error = |message: &str| DiceError {
+            expr: text.to_string(),
+            message: message.to_string(),
+        };
+        let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_lowercase();
+        if compact.is_empty() {
+            return Err(error("empty expression"));
+        }
+
+        let mut terms = Vec::new();
+        let mut rest = compact.as_str();
+        let mut negative = false;
+        if let Some(stripped) = rest.strip_prefix('-') {
+            negative = true;
+            rest = stripped;
+        }
+        loop {
+            let end = rest.find(['+', '-']).unwrap_or(rest.len());
+            let (term, tail) = rest.split_at(end);
+            terms.push(Self::parse_term(term, negative).map_err(|m| error(&m))?);
+            match tail.chars().next() {
+                Some(sign) => {
+                    negative = sign == '-';
+                    rest = &tail[1..];
+                }
+                None => break,
+            }
+        }
+        Ok(DiceExpr { terms })
+    }
+
+    fn parse_term(term: &str, negative: bool) -> Result<Term, String> {
+        let number = |text: &str, what: &str, limit: i32| -> Result<i32, String> {
+            if text.is_empty() || !text.chars().all(|c| c.is_ascii_digit()) {
+                return Err(format!("bad {} '{}'", what, text));
+            }
+            // all digits, so this only fails for numbers too large for an i32
+            match text.parse::<i32>() {
+                Ok(value) if value <= limit => Ok(value),
+                _ => Err(format!("{} {} is more than {}", what, text, limit)),
+            }
+        };
+        let Some(d) = term.find('d') else {
+            let value = number(term, "number", MAX_CONSTANT)?;
+            return Ok(Term::Constant(if negative { -value } else { value }));
+        };
+
+        let count = if d == 0 { 1 } else { number(&term[..d], "dice count", MAX_DICE)? as u32 };
+        let rest = &term[d + 1..];
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let sides = number(&rest[..digits], "number of sides", MAX_SIDES)? as u32;
+        let mut suffix = &rest[digits..];
+        if count == 0 || sides == 0 {
+            return Err(format!("'{}' rolls no dice", term));
+        }
+
+        let mut explode = false;
+        let mut keep = None;
+        while !suffix.is_empty() {
+            if let Some(tail) = suffix.strip_prefix('!') {
+                if sides == 1 {
+                    return Err("a d1 cannot explode".to_string());
+                }
+                explode = true;
+                suffix = tail;
+            } else if let Some(tail) = suffix.strip_prefix("adv") {
+                keep = Some(Keep::Advantage);
+                suffix = tail;
+            } else if let Some(tail) = suffix.strip_prefix("dis") {
+                keep = Some(Keep::Disadvantage);
+                suffix = tail;
+            } else if let Some(tail) = suffix.strip_prefix("kh").or_else(|| suffix.strip_prefix("kl")) {
+                let digits = tail.find(|c: char| !c.is_ascii_digit()).unwrap_or(tail.len());
+                let n = number(&tail[..digits], "keep count", MAX_DICE)? as u32;
+                if n == 0 || n > count {
+                    return Err(format!("cannot keep {} of {} dice", n, count));
+                }
+                keep = Some(if suffix.starts_with("kh") { Keep::Highest(n) } else { Keep::Lowest(n) });
+                suffix = &tail[digits..];
+            } else {
+                return Err(format!("unknown modifier '{}'", suffix));
+            }
+        }
+        Ok(Term::Dice { negative, count, sides, keep, explode })
+    }
+
+    pub fn roll<R: Rng + ?Sized>(&self, rng: &mut R) -> Roll {
+        let mut dice = Vec::new();
+        let mut modifier = 0;
+        let mut total = 0;
+        for term in &self.terms {
+            match *term {
+                Term::Constant(value) => {
+                    modifier += value;
+                    total += value;
+                }
+                Term::Dice { negative, count, sides, keep, explode } => {
+                    let mut set = roll_set(count, sides, explode, rng);
+                    match keep {
+                        Some(Keep::Highest(n)) => drop_all_but(&mut set, n, true),
+                        Some(Keep::Lowest(n)) => drop_all_but(&mut set, n, false),
+                        Some(Keep::Advantage) | Some(Keep::Disadvantage) => {
+                            let mut other = roll_set(count, sides, explode, rng);
+                            let (first, second) = (kept_sum(&set), kept_sum(&other));
+                            let take_other = match keep {
+                                Some(Keep::Advantage) => second > first,
+                                _ => second < first,
+                            };
+                            let dropped = if take_other { &mut set } else { &mut other };
+                            dropped.iter_mut().for_each(|d| d.kept = false);
+                            set.append(&mut other);
+                        }
+                        None => {}
+                    }
+                    let sum = kept_sum(&set);
+                    total += if negative { -sum } else { sum };
+                    dice.append(&mut set);
+                }
+            }
+        }
+        Roll { dice, modifier, total }
+    }
+
+    /// The largest total the expression can produce without exploding.
+    pub fn maximum(&self) -> i32 {
+        self.terms
+            .iter()
+            .map(|term| match *term {
+                Term::Constant(value) => value,
+                Term::Dice { negative, count, sides, keep, .. } => {
+                    let kept = match keep {
+                        Some(Keep::Highest(n)) | Some(Keep::Lowest(n)) => n,
+                        _ => count,
+                    };
+                    // a subtracted term is at its largest when every die rolls a one
+                    if negative { -(kept as i32) } else { (kept * sides) as i32 }
+                }
+            })
+            .sum()
+    }
+}
+
+impl FromStr for DiceExpr {
+    type Err = DiceError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        DiceExpr::parse(text)
+    }
+}
+
+impl TryFrom<String> for DiceExpr {
+    type Error = DiceError;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        DiceExpr::parse(&text)
+    }
+}
+
+impl From<DiceExpr> for String {
+    fn from(expr: DiceExpr) -> Self {
+        expr.to_string()
+    }
+}
+
+impl fmt::Display for DiceExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, term) in self.terms.iter().enumerate() {
+            let negative = match *term {
+                Term::Constant(value) => value < 0,
+                Term::Dice { negative, .. } => negative,
+            };
+            if negative {
+                write!(f, "-")?;
+            } else if i > 0 {
+                write!(f, "+")?;
+            }
+            match *term {
+                Term::Constant(value) => write!(f, "{}", value.abs())?,
+                Term::Dice { count, sides, keep, explode, .. } => {
+                    write!(f, "{}d{}", count, sides)?;
+                    if explode {
+                        write!(f, "!")?;
+                    }
+                    match keep {
+                        Some(Keep::Highest(n)) => write!(f, "kh{}", n)?,
+                        Some(Keep::Lowest(n)) => write!(f, "kl{}", n)?,
+                        Some(Keep::Advantage) => write!(f, "adv")?,
+                        Some(Keep::Disadvantage) => write!(f, "dis")?,
+                        None => {}
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expr(text: &str) -> DiceExpr {
+        DiceExpr::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display_round_trip() {
+        for text in ["2d6+3", "4d6kh3", "1d20adv", "1d20dis", "3d8!", "1d8+1d4-2", "2d20kl1", "-1d4+10"] {
+            assert_eq!(expr(text).to_string(), text);
+        }
+        assert_eq!(expr("d20").to_string(), "1d20");
+        assert_eq!(expr(" 2D6 + 3 ").to_string(), "2d6+3");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for text in ["", "2d", "d0", "0d6", "2d6x", "4d6kh5", "1d1!", "2d6+", "1d3000000000", "1d6+3000000000", "1000000000d6", "101d6", "1d1001"] {
+            assert!(DiceExpr::parse(text).is_err(), "{} should not parse", text);
+        }
+    }
+
+    #[test]
+    fn test_rolls_stay_in_bounds() {
+        let mut rng = seeded_rng(5);
+        for _ in 0..200 {
+            let roll = expr("2d6+3").roll(&mut rng);
+            assert!((5..=15).contains(&roll.total));
+            assert_eq!(roll.dice.len(), 2);
+
+            let roll = expr("4d6kh3").roll(&mut rng);
+            assert_eq!(roll.dice.iter().filter(|d| d.kept).count(), 3);
+            assert!((3..=18).contains(&roll.total));
+
+            let roll = expr("1d20adv").roll(&mut rng);
+            assert_eq!(roll.dice.len(), 2);
+            assert_eq!(roll.total, roll.dice.iter().map(|d| d.value).max().unwrap());
+
+            let roll = expr("3d8!").roll(&mut rng);
+            assert!(roll.dice.len() >= 3);
+            assert_eq!(roll.total, roll.dice.iter().map(|d| d.value).sum::<i32>());
+        }
+    }
+
+    #[test]
+    fn test_maximum() {
+        assert_eq!(expr("2d6+3").maximum(), 15);
+        assert_eq!(expr("4d6kh3").maximum(), 18);
+        assert_eq!(expr("1d8-1d4").maximum(), 7);
+    }
+}
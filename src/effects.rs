@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// Loses its turns while it lasts.
+    Stunned,
+    /// Takes `potency` damage every turn; further doses add up.
+    Poisoned,
+    /// Moves `potency` slower, the strongest slow wins.
+    Slowed,
+    /// Takes `potency` damage every turn; every wound bleeds on its own.
+    Bleeding,
+    /// Defends `potency` better, the strongest shield wins.
+    Shielded,
+}
+
+/// How a new effect combines with one of the same condition already on a player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stacking {
+    /// Keep one instance with the longer duration and the higher potency.
+    Refresh,
+    /// Keep one instance with the longer duration and the potencies added.
+    Intensify,
+    /// Every application runs as its own instance.
+    Independent,
+}
+
+impl Condition {
+    pub fn stacking(&self) -> Stacking {
+        match self {
+            Condition::Stunned | Condition::Slowed | Condition::Shielded => Stacking::Refresh,
+            Condition::Poisoned => Stacking::Intensify,
+            Condition::Bleeding => Stacking::Independent,
+        }
+    }
+    pub fn deals_damage(&self) -> bool {
+        matches!(self, Condition::Poisoned | Condition::Bleeding)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Condition::Stunned => "stunned",
+            Condition::Poisoned => "poisoned",
+            Condition::Slowed => "slowed",
+            Condition::Bleeding => "bleeding",
+            Condition::Shielded => "shielded",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn one() -> i32 {
+    1
+}
+
+/// A condition lasting `turns` of the affected player's own turns.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StatusEffect {
+    pub condition: Condition,
+    pub turns: i32,
+    #[serde(default = "one")]
+    pub potency: i32,
+    /// Who inflicted the effect, credited if it finishes the player off.
+    #[serde(skip)]
+    pub source: String,
+}
+
+impl StatusEffect {
+    pub fn new(condition: Condition, turns: i32, potency: i32, source: &str) -> Self {
+        StatusEffect {
+            condition,
+            turns,
+            potency,
+            source: source.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for StatusEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.condition)?;
+        if self.potency != 1 {
+            write!(f, " x{}", self.potency)?;
+        }
+        write!(f, " ({})", self.turns)
+    }
+}
+
+/// Add `effect` to `effects` following its condition's stacking rule.
+pub fn stack(effects: &mut Vec<StatusEffect>, effect: StatusEffect) {
+    let existing = effects.iter_mut().find(|e| e.condition == effect.condition);
+    match (effect.condition.stacking(), existing) {
+        (Stacking::Refresh, Some(existing)) => {
+            existing.turns = existing.turns.max(effect.turns);
+            existing.potency = existing.potency.max(effect.potency);
+            existing.source = effect.source;
+        }
+        (Stacking::Intensify, Some(existing)) => {
+            existing.turns = existing.turns.max(effect.turns);
+            existing.potency += effect.potency;
+            existing.source = effect.source;
+        }
+        _ => effects.push(effect),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stacking_rules() {
+        let mut effects = Vec::new();
+        stack(&mut effects, StatusEffect::new(Condition::Poisoned, 2, 1, "A"));
+        stack(&mut effects, StatusEffect::new(Condition::Poisoned, 3, 2, "B"));
+        stack(&mut effects, StatusEffect::new(Condition::Slowed, 4, 3, "A"));
+        stack(&mut effects, StatusEffect::new(Condition::Slowed, 1, 1, "B"));
+        stack(&mut effects, StatusEffect::new(Condition::Bleeding, 2, 1, "A"));
+        stack(&mut effects, StatusEffect::new(Condition::Bleeding, 2, 1, "A"));
+
+        assert_eq!(effects.len(), 4);
+        assert_eq!((effects[0].turns, effects[0].potency, effects[0].source.as_str()), (3, 3, "B"));
+        assert_eq!((effects[1].turns, effects[1].potency), (4, 3));
+        assert_eq!(effects[0].to_string(), "poisoned x3 (3)");
+    }
+}
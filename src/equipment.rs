@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::dice::DiceExpr;
+use crate::effects::StatusEffect;
+
+/// Up to `reach` away a weapon hits with an extra `to_hit`, usually negative at long range.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RangeBand {
+    pub reach: i32,
+    #[serde(default)]
+    pub to_hit: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Weapon {
+    pub damage: DiceExpr,
+    #[serde(default)]
+    pub to_hit: i32,
+    /// Range bands from nearest to farthest, the last one is the weapon's reach.
+    pub ranges: Vec<RangeBand>,
+    /// Overrides the rules' critical range for this weapon.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crit_range: Option<i32>,
+    /// Inflicted on the target with every hit.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<StatusEffect>,
+}
+
+impl Weapon {
+    pub fn reach(&self) -> i32 {
+        self.ranges.iter().map(|band| band.reach).max().unwrap_or(0)
+    }
+    /// The to-hit modifier against a target `distance` away, `None` when out of reach.
+    pub fn to_hit_at(&self, distance: f32) -> Option<i32> {
+        self.ranges
+            .iter()
+            .find(|band| distance <= band.reach as f32)
+            .map(|band| self.to_hit + band.to_hit)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ArmorItem {
+    pub defense: i32,
+}
+
+/// A potion or similar item that restores armor, good for `charges` uses.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Consumable {
+    pub heal: DiceExpr,
+    pub charges: u32,
+}
+
+/// The weapons, armor and consumables players can be equipped with, keyed by name.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ItemCatalog {
+    #[serde(default)]
+    pub weapons: BTreeMap<String, Weapon>,
+    #[serde(default)]
+    pub armor: BTreeMap<String, ArmorItem>,
+    #[serde(default)]
+    pub consumables: BTreeMap<String, Consumable>,
+}
+
+/// The item names a player config refers to.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct EquipmentConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weapon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub armor: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub consumables: Vec<String>,
+}
+
+impl EquipmentConfig {
+    pub fn is_empty(&self) -> bool {
+        self.weapon.is_none() && self.armor.is_none() && self.consumables.is_empty()
+    }
+}
+
+/// A consumable a player carries, with the charges it has left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsumableSlot {
+    pub name: String,
+    pub item: Consumable,
+    pub charges: u32,
+}
+
+/// What a player carries into battle, looked up from the catalog.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Equipment {
+    pub weapon: Option<(String, Weapon)>,
+    pub armor: Option<(String, ArmorItem)>,
+    pub consumables: Vec<ConsumableSlot>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownItem {
+    pub kind: &'static str,
+    pub name: String,
+}
+
+impl fmt::Display for UnknownItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no {} named '{}' in the item catalog", self.kind, self.name)
+    }
+}
+
+impl std::error::Error for UnknownItem {}
+
+impl ItemCatalog {
+    pub fn equip(&self, config: &EquipmentConfig) -> Result<Equipment, UnknownItem> {
+        let weapon = match &config.weapon {
+            Some(name) => {
+                let weapon = self.weapons.get(name).ok_or_else(|| UnknownItem {
+                    kind: "weapon",
+                    name: name.clone(),
+                })?;
+                Some((name.clone(), weapon.clone()))
+            }
+            None => None,
+        };
+        let armor = match &config.armor {
+            Some(name) => {
+                let armor = self.armor.get(name).ok_or_else(|| UnknownItem {
+                    kind: "armor",
+                    name: name.clone(),
+                })?;
+                Some((name.clone(), armor.clone()))
+            }
+            None => None,
+        };
+        let consumables = config
+            .consumables
+            .iter()
+            .map(|name| {
+                let item = self.consumables.get(name).ok_or_else(|| UnknownItem {
+                    kind: "consumable",
+                    name: name.clone(),
+                })?;
+                Ok(ConsumableSlot {
+                    name: name.clone(),
+                    item: item.clone(),
+                    charges: item.charges,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Equipment { weapon, armor, consumables })
+    }
+    /// Add the items `equipment` carries, so a catalog can be rebuilt from a roster.
+    pub fn insert(&mut self, equipment: &Equipment) {
+        if let Some((name, weapon)) = &equipment.weapon {
+            self.weapons.insert(name.clone(), weapon.clone());
+        }
+        if let Some((name, armor)) = &equipment.armor {
+            self.armor.insert(name.clone(), armor.clone());
+        }
+        for slot in &equipment.consumables {
+            self.consumables.insert(slot.name.clone(), slot.item.clone());
+        }
+    }
+}
+
+impl From<&Equipment> for EquipmentConfig {
+    fn from(equipment: &Equipment) -> Self {
+        EquipmentConfig {
+            weapon: equipment.weapon.as_ref().map(|(name, _)| name.clone()),
+            armor: equipment.armor.as_ref().map(|(name, _)| name.clone()),
+            consumables: equipment.consumables.iter().map(|slot| slot.name.clone()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CATALOG: &str = "
+weapons:
+  longbow:
+    damage: 1d8
+    ranges:
+      - reach: 10
+      - reach: 25
+        to_hit: -4
+  greataxe:
+    damage: 1d12
+    to_hit: 1
+    ranges:
+      - reach: 2
+armor:
+  chainmail:
+    defense: 4
+consumables:
+  potion:
+    heal: 2d4+2
+    charges: 2
+";
+
+    #[test]
+    fn test_weapon_range_bands() {
+        let catalog: ItemCatalog = serde_yaml::from_str(CATALOG).unwrap();
+        let longbow = &catalog.weapons["longbow"];
+
+        assert_eq!(longbow.reach(), 25);
+        assert_eq!(longbow.to_hit_at(5.0), Some(0));
+        assert_eq!(longbow.to_hit_at(20.0), Some(-4));
+        assert_eq!(longbow.to_hit_at(30.0), None);
+        assert_eq!(catalog.weapons["greataxe"].to_hit_at(1.5), Some(1));
+    }
+
+    #[test]
+    fn test_equip_looks_up_items() {
+        let catalog: ItemCatalog = serde_yaml::from_str(CATALOG).unwrap();
+        let config = EquipmentConfig {
+            weapon: Some("greataxe".to_string()),
+            armor: Some("chainmail".to_string()),
+            consumables: vec!["potion".to_string()],
+        };
+        let equipment = catalog.equip(&config).unwrap();
+
+        assert_eq!(equipment.consumables[0].charges, 2);
+        assert_eq!(equipment.armor.unwrap().1.defense, 4);
+        let missing = EquipmentConfig {
+            weapon: Some("spoon".to_string()),
+            ..EquipmentConfig::default()
+        };
+        assert_eq!(catalog.equip(&missing).unwrap_err().name, "spoon");
+    }
+}
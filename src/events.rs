@@ -0,0 +1,284 @@
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use tracing::{debug, info, warn};
+
+use crate::effects::Condition;
+use crate::player::Location;
+
+/// A fact emitted by the engine while a battle is played.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum GameEvent {
+    /// `who` is up, before anything its effects do at the start of its turn.
+    TurnStarted {
+        who: String,
+    },
+    Moved {
+        who: String,
+        from: Location,
+        to: Location,
+        /// The rest of the route `who` is following, empty for straight moves.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        route: Vec<Location>,
+    },
+    AttackRolled {
+        attacker: String,
+        target: String,
+        roll: i32,
+        #[serde(default)]
+        natural: i32,
+        bonus: i32,
+        /// The target's defense, cover included.
+        target_defense: i32,
+        /// How much of the defense came from cover between attacker and target.
+        #[serde(default)]
+        cover: i32,
+        #[serde(default)]
+        critical: bool,
+        /// A natural roll low enough to miss whatever the bonus.
+        #[serde(default)]
+        fumble: bool,
+    },
+    /// A wall between `attacker` and `target` stopped the attack.
+    AttackBlocked {
+        attacker: String,
+        target: String,
+    },
+    Damaged {
+        who: String,
+        by: String,
+        amount: i32,
+        remaining: i32,
+        #[serde(default)]
+        critical: bool,
+    },
+    Defeated {
+        who: String,
+        by: String,
+    },
+    /// `who` walked off a drop of `height` and got hurt landing.
+    Fell {
+        who: String,
+        height: f32,
+        damage: i32,
+        remaining: i32,
+    },
+    Held {
+        who: String,
+    },
+    /// `who` used one of its abilities, the events it caused follow.
+    AbilityUsed {
+        who: String,
+        ability: String,
+        target: String,
+    },
+    /// `who` used up a charge of a consumable.
+    ItemUsed {
+        who: String,
+        item: String,
+        charges_left: u32,
+    },
+    Healed {
+        who: String,
+        by: String,
+        amount: i32,
+        remaining: i32,
+    },
+    EffectApplied {
+        who: String,
+        by: String,
+        condition: Condition,
+        turns: i32,
+        potency: i32,
+    },
+    /// A damaging effect hurt `who` at the start of its turn.
+    EffectTicked {
+        who: String,
+        condition: Condition,
+        damage: i32,
+        remaining: i32,
+    },
+    EffectExpired {
+        who: String,
+        condition: Condition,
+    },
+    /// `who` lost its turn to a stun.
+    Stunned {
+        who: String,
+    },
+    MoraleChecked {
+        who: String,
+        roll: i32,
+        morale: i32,
+        passed: bool,
+    },
+    /// `who` broke and is running for it, it no longer counts as holding the field.
+    Routed {
+        who: String,
+    },
+    Surrendered {
+        who: String,
+    },
+    /// A routed player got away and left the battle.
+    Escaped {
+        who: String,
+    },
+    /// A new round began, `order` lists the players by initiative.
+    RoundStarted {
+        round: i32,
+        order: Vec<String>,
+    },
+    TurnLimitReached {
+        turns: i32,
+    },
+}
+
+impl GameEvent {
+    /// Whether an `AttackRolled` event landed; `false` for every other event.
+    pub fn is_hit(&self) -> bool {
+        match self {
+            GameEvent::AttackRolled { roll, bonus, target_defense, critical, fumble, .. } => {
+                !fumble && (*critical || roll + bonus >= *target_defense)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for GameEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameEvent::TurnStarted { who } => write!(f, "{} takes a turn", who),
+            GameEvent::Moved { who, from, to, .. } => write!(f, "{} moved from {} to {}", who, from, to),
+            GameEvent::AttackRolled { attacker, target, roll, bonus, target_defense, cover, critical, fumble, .. } => {
+                write!(f, "{} attacks {}: {}{:+} vs defense {}", attacker, target, roll, bonus, target_defense)?;
+                if *cover > 0 {
+                    write!(f, " ({} from cover)", cover)?;
+                }
+                let result = match (critical, fumble) {
+                    (true, _) => "critical hit!",
+                    (_, true) => "fumble!",
+                    _ if self.is_hit() => "hit",
+                    _ => "miss",
+                };
+                write!(f, ", {}", result)
+            }
+            GameEvent::AttackBlocked { attacker, target } => {
+                write!(f, "{} has no line of sight to {}", attacker, target)
+            }
+            GameEvent::Damaged { who, by, amount, remaining, critical } => write!(
+                f,
+                "{} {}hit {} for {} damage, {} left",
+                by,
+                if *critical { "critically " } else { "" },
+                who,
+                amount,
+                remaining
+            ),
+            GameEvent::Defeated { who, by } if who == by => write!(f, "{} fell to its death", who),
+            GameEvent::Defeated { who, by } => write!(f, "{} defeated {}", by, who),
+            GameEvent::Fell { who, height, damage, remaining } => write!(
+                f,
+                "{} fell {:.1} and took {} damage, {} left",
+                who, height, damage, remaining
+            ),
+            GameEvent::Held { who } => write!(f, "{} holds position", who),
+            GameEvent::AbilityUsed { who, ability, target } if who == target => {
+                write!(f, "{} uses {}", who, ability)
+            }
+            GameEvent::AbilityUsed { who, ability, target } => {
+                write!(f, "{} uses {} on {}", who, ability, target)
+            }
+            GameEvent::ItemUsed { who, item, charges_left } => {
+                write!(f, "{} uses {}, {} left", who, item, charges_left)
+            }
+            GameEvent::Healed { who, by, amount, remaining } if who == by => {
+                write!(f, "{} recovered {}, {} left", who, amount, remaining)
+            }
+            GameEvent::Healed { who, by, amount, remaining } => {
+                write!(f, "{} healed {} for {}, {} left", by, who, amount, remaining)
+            }
+            GameEvent::EffectApplied { who, by, condition, turns, potency } => write!(
+                f,
+                "{} left {} {} (x{}) for {} turns",
+                by, who, condition, potency, turns
+            ),
+            GameEvent::EffectTicked { who, condition, damage, remaining } => write!(
+                f,
+                "{} took {} damage from being {}, {} left",
+                who, damage, condition, remaining
+            ),
+            GameEvent::EffectExpired { who, condition } => {
+                write!(f, "{} is no longer {}", who, condition)
+            }
+            GameEvent::Stunned { who } => write!(f, "{} is stunned and loses the turn", who),
+            GameEvent::MoraleChecked { who, roll, morale, passed } => write!(
+                f,
+                "{} tests morale: {} vs {}, {}",
+                who,
+                roll,
+                morale,
+                if *passed { "holds" } else { "breaks" }
+            ),
+            GameEvent::Routed { who } => write!(f, "{} is routed!", who),
+            GameEvent::Surrendered { who } => write!(f, "{} surrenders", who),
+            GameEvent::Escaped { who } => write!(f, "{} fled the field", who),
+            GameEvent::RoundStarted { round, order } => {
+                write!(f, "Round {} begins: {}", round, order.join(", "))
+            }
+            GameEvent::TurnLimitReached { turns } => {
+                write!(f, "Battle reached maximum turns: {}", turns)
+            }
+        }
+    }
+}
+
+/// Receives every event the engine emits, tagged with the turn it happened in.
+pub trait Observer {
+    fn on_event(&mut self, turn: i32, event: &GameEvent);
+}
+
+/// Lets an observer be shared between the game and whoever reads it back.
+impl<T: Observer> Observer for Rc<RefCell<T>> {
+    fn on_event(&mut self, turn: i32, event: &GameEvent) {
+        self.borrow_mut().on_event(turn, event);
+    }
+}
+
+/// Writes events to the `tracing` log, used by the CLI.
+pub struct LogObserver;
+
+impl Observer for LogObserver {
+    fn on_event(&mut self, turn: i32, event: &GameEvent) {
+        match event {
+            GameEvent::Defeated { .. }
+            | GameEvent::Routed { .. }
+            | GameEvent::Surrendered { .. }
+            | GameEvent::TurnLimitReached { .. } => {
+                warn!("[{}] {}", turn, event)
+            }
+            GameEvent::TurnStarted { .. } => debug!("[{}] {}", turn, event),
+            _ => info!("[{}] {}", turn, event),
+        }
+    }
+}
+
+/// Buffers events until they are drained by the consumer.
+#[derive(Default)]
+pub struct EventLog {
+    events: Vec<(i32, GameEvent)>,
+}
+
+impl EventLog {
+    pub fn drain(&mut self) -> Vec<(i32, GameEvent)> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+impl Observer for EventLog {
+    fn on_event(&mut self, turn: i32, event: &GameEvent) {
+        self.events.push((turn, event.clone()));
+    }
+}
@@ -1,70 +1,1165 @@
 use crate::MAX_TURNS;
-use crate::player;
-use core::f32;
-use rand::rngs::ThreadRng;
-use std::collections::VecDeque;
-use tracing::{info, warn};
+use crate::events::{GameEvent, Observer};
+use crate::abilities::{self, AbilityKind};
+use crate::arena::{Arena, Footprint, Sight};
+use crate::behavior::Intent;
+use crate::dice::DiceExpr;
+use crate::morale::{self, MoraleState};
+use crate::effects::{Condition, StatusEffect};
+use crate::player::{self, Location};
+use crate::rules::Rules;
+use crate::targeting::{Nearest, Targeting, TargetingStrategy};
+use rand::Rng;
+use std::collections::{HashMap, VecDeque};
+use tracing::warn;
+
+/// Scheduler time that makes up one round.
+const TICKS_PER_ROUND: i32 = 100;
+/// A player with this speed acts exactly once per round.
+const BASE_SPEED: i32 = 10;
+
+/// Ticks a player has to wait between actions, faster players act more often.
+fn action_delay(speed: i32) -> i32 {
+    TICKS_PER_ROUND * BASE_SPEED / speed.max(1)
+}
+
+/// What happened during a single call to `Game::step`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TurnOutcome {
+    Moved {
+        player: String,
+        target: String,
+        distance: f32,
+    },
+    Missed {
+        attacker: String,
+        target: String,
+    },
+    Hit {
+        attacker: String,
+        target: String,
+        damage: i32,
+        critical: bool,
+        defeated: bool,
+    },
+    Retreated {
+        player: String,
+        target: String,
+        distance: f32,
+    },
+    Held {
+        player: String,
+    },
+    UsedAbility {
+        player: String,
+        ability: String,
+        target: String,
+    },
+    UsedItem {
+        player: String,
+        item: String,
+    },
+    /// The acting player lost its turn to a stun.
+    Stunned { player: String },
+    /// The acting player was finished off by its own effects before it could act.
+    Succumbed { player: String },
+    /// The acting player had nobody to fight.
+    Idle { player: String },
+    /// The battle was already over, no turn was taken.
+    Over,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BattleStatus {
+    Ongoing,
+    Victory,
+    TurnLimitReached,
+}
 
 pub struct Game {
+    /// The current round; every player acts at least once a round at base speed.
     pub turns: i32,
+    pub rules: Rules,
+    pub arena: Arena,
     pub players: VecDeque<player::Player>,
+    /// Players that were defeated, in the order they fell.
+    pub fallen: Vec<player::Player>,
+    /// Players that surrendered or fled the field.
+    pub withdrawn: Vec<player::Player>,
+    /// The target each team agreed to focus fire on.
+    focus: HashMap<String, String>,
+    observers: Vec<Box<dyn Observer>>,
 }
 
 impl Game {
     pub fn new() -> Self {
         Game {
             turns: 0,
+            rules: Rules::default(),
+            arena: Arena::default(),
             players: VecDeque::new(),
+            fallen: Vec::new(),
+            withdrawn: Vec::new(),
+            focus: HashMap::new(),
+            observers: Vec::new(),
+        }
+    }
+    /// Pick a target for `source` with its targeting strategy.
+    pub fn select_target<R: Rng>(&mut self, source: &player::Player, rng: &mut R) -> Option<usize> {
+        let focus = self.focus.get(source.side()).map(String::as_str);
+        let idx = source.targeting.strategy().select(source, &self.players, focus, rng)?;
+        if source.targeting == Targeting::FocusFire {
+            self.focus.insert(source.side().to_string(), self.players[idx].name.clone());
+        }
+        Some(idx)
+    }
+    /// The players still holding the field: everybody who has not routed, or all that
+    /// are left when every one of them has.
+    fn contenders(&self) -> Vec<&player::Player> {
+        let steady: Vec<&player::Player> = self.players.iter().filter(|p| p.is_steady()).collect();
+        if steady.is_empty() {
+            self.players.iter().collect()
+        } else {
+            steady
+        }
+    }
+    /// The battle is won once every contender is on the same side.
+    pub fn status(&self) -> BattleStatus {
+        let contenders = self.contenders();
+        let first = contenders.first();
+        if contenders.iter().all(|p| first.is_some_and(|first| first.is_ally(p))) {
+            BattleStatus::Victory
+        } else if self.next_round() > MAX_TURNS {
+            BattleStatus::TurnLimitReached
+        } else {
+            BattleStatus::Ongoing
+        }
+    }
+    /// The team left standing once the battle is won; a lone player's team is its name.
+    pub fn winning_side(&self) -> Option<&str> {
+        match self.status() {
+            BattleStatus::Victory => self.contenders().first().map(|p| p.side()),
+            _ => None,
+        }
+    }
+    /// The round the next player to act will act in.
+    fn next_round(&self) -> i32 {
+        let next = self.players.iter().map(|p| p.ready_at).min().unwrap_or(0);
+        next / TICKS_PER_ROUND + 1
+    }
+    /// Index of the player who acts next: whoever is ready first, ties going to the
+    /// higher initiative roll and then to roster order.
+    fn next_actor(&self) -> Option<usize> {
+        self.players
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, p)| (p.ready_at, -p.initiative))
+            .map(|(idx, _)| idx)
+    }
+    /// Roll initiative plus speed bonus for everybody still standing.
+    fn start_round<R: Rng>(&mut self, round: i32, rng: &mut R) {
+        self.turns = round;
+        for player in self.players.iter_mut() {
+            player.initiative = self.rules.initiative.roll(rng).total + player.speed.bonus();
+        }
+        let mut order: Vec<&player::Player> = self.players.iter().collect();
+        order.sort_by_key(|p| -p.initiative);
+        let order = order.iter().map(|p| p.name.clone()).collect();
+        self.emit(GameEvent::RoundStarted { round, order });
+    }
+    pub fn subscribe(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+    fn emit(&mut self, event: GameEvent) {
+        for observer in self.observers.iter_mut() {
+            observer.on_event(self.turns, &event);
+        }
+    }
+    /// Play a single turn for the next player to act.
+    pub fn step<R: Rng>(&mut self, rng: &mut R) -> TurnOutcome {
+        if self.status() != BattleStatus::Ongoing {
+            return TurnOutcome::Over;
+        }
+        let round = self.next_round();
+        if round > self.turns {
+            self.start_round(round, rng);
+        }
+        let slot = self.next_actor().unwrap();
+        let mut player = self.players.remove(slot).unwrap();
+        let mut events = vec![GameEvent::TurnStarted {
+            who: player.name.clone(),
+        }];
+        let stunned = player.has_condition(Condition::Stunned);
+        if let Some(by) = Self::tick_effects(&mut player, &mut events) {
+            let outcome = TurnOutcome::Succumbed {
+                player: player.name.clone(),
+            };
+            events.push(GameEvent::Defeated {
+                who: player.name.clone(),
+                by,
+            });
+            self.allies_lose_heart(&player.name, player.side(), &mut events, rng);
+            self.fallen.push(player);
+            self.clear_field();
+            return self.finish_turn(events, outcome);
+        }
+        let routed = player.morale_state == MoraleState::Routed;
+        let target = if stunned {
+            None
+        } else if routed {
+            Nearest.select(&player, &self.players, None, rng)
+        } else {
+            self.select_target(&player, rng)
+        };
+        let outcome = match target {
+            Some(idx) if routed => self.flee(&mut player, idx, &mut events, rng),
+            Some(idx) => {
+                let intent = player.behavior.behavior().decide(&player, &self.players[idx], &self.arena);
+                if let Some(item) = player.consumable_to_use() {
+                    Self::use_consumable(&mut player, item, &mut events, rng)
+                } else {
+                    match abilities::choose(&player, intent, &self.players[idx], &self.players) {
+                        Some(ability) => self.use_ability(&mut player, ability, idx, &mut events, rng),
+                        None => self.act(&mut player, intent, idx, &mut events, rng),
+                    }
+                }
+            }
+            None if stunned => {
+                events.push(GameEvent::Stunned {
+                    who: player.name.clone(),
+                });
+                TurnOutcome::Stunned {
+                    player: player.name.clone(),
+                }
+            }
+            None => {
+                warn!("{} has nobody to fight", player.name);
+                TurnOutcome::Idle {
+                    player: player.name.clone(),
+                }
+            }
+        };
+        player.tick_cooldowns();
+        player.ready_at += action_delay(player.speed.curr);
+        self.players.insert(slot, player);
+        self.clear_field();
+        self.finish_turn(events, outcome)
+    }
+    /// Carry out what the player's behaviour decided against the player at `idx`.
+    fn act<R: Rng>(
+        &mut self,
+        player: &mut player::Player,
+        intent: Intent,
+        idx: usize,
+        events: &mut Vec<GameEvent>,
+        rng: &mut R,
+    ) -> TurnOutcome {
+        let target = self.players[idx].name.clone();
+        let threat = self.players[idx].loc.clone();
+        match intent {
+            Intent::Attack => {
+                let dice = player.damage_dice(&self.rules).clone();
+                let effects = match &player.equipment.weapon {
+                    Some((_, weapon)) => weapon.effects.clone(),
+                    None => Vec::new(),
+                };
+                self.strike(player, idx, &dice, &effects, events, rng)
+            }
+            Intent::Advance => {
+                let distance = player.loc.distance(&threat);
+                let from = player.loc.clone();
+                let fall = player.move_towards(&self.players[idx], &self.arena, &self.bystanders(player, idx));
+                self.report_move(player, from, fall, events, rng);
+                TurnOutcome::Moved {
+                    player: player.name.clone(),
+                    target,
+                    distance,
+                }
+            }
+            Intent::Retreat { .. } | Intent::Flee => {
+                let distance = match intent {
+                    Intent::Retreat { distance } => distance,
+                    _ => player.speed.curr as f32,
+                };
+                let from = player.loc.clone();
+                let fall = player.move_away(&threat, distance, &self.arena, &self.crowd(player));
+                self.report_move(player, from, fall, events, rng);
+                TurnOutcome::Retreated {
+                    player: player.name.clone(),
+                    target,
+                    distance,
+                }
+            }
+            Intent::Hold => {
+                events.push(GameEvent::Held {
+                    who: player.name.clone(),
+                });
+                TurnOutcome::Held {
+                    player: player.name.clone(),
+                }
+            }
+        }
+    }
+    /// Report the move `player` made from `from`, hurting it if it dropped further than a
+    /// safe fall on the way.
+    fn report_move<R: Rng>(
+        &mut self,
+        player: &mut player::Player,
+        from: Location,
+        fall: f32,
+        events: &mut Vec<GameEvent>,
+        rng: &mut R,
+    ) {
+        events.push(GameEvent::Moved {
+            who: player.name.clone(),
+            from,
+            to: player.loc.clone(),
+            route: player.route.clone(),
+        });
+        if fall <= self.rules.safe_fall {
+            return;
+        }
+        let rolls = (fall / self.rules.safe_fall.max(1.0)) as u32;
+        let damage: i32 = (0..rolls).map(|_| self.rules.fall_damage.roll(rng).total.max(0)).sum();
+        player.armor.curr -= damage;
+        events.push(GameEvent::Fell {
+            who: player.name.clone(),
+            height: fall,
+            damage,
+            remaining: player.armor.curr,
+        });
+        if player.is_dead() {
+            events.push(GameEvent::Defeated {
+                who: player.name.clone(),
+                by: player.name.clone(),
+            });
+            self.allies_lose_heart(&player.name, player.side(), events, rng);
+        }
+    }
+    /// The room taken up by everybody still standing apart from `player`, who is acting.
+    fn crowd(&self, player: &player::Player) -> Vec<Footprint> {
+        self.players
+            .iter()
+            .filter(|p| !p.is_dead())
+            .map(|p| p.footprint(player))
+            .collect()
+    }
+    /// The crowd around `player`, leaving out the player at `idx` too.
+    fn bystanders(&self, player: &player::Player, idx: usize) -> Vec<Footprint> {
+        self.players
+            .iter()
+            .enumerate()
+            .filter(|&(i, p)| i != idx && !p.is_dead())
+            .map(|(_, p)| p.footprint(player))
+            .collect()
+    }
+    /// Roll an attack against the player at `idx`, dealing `dice` damage and inflicting
+    /// `effects` if it lands. A wall in the way stops the attack, cover and other players
+    /// make it harder. Defeated players stay in the roster until the turn ends.
+    fn strike<R: Rng>(
+        &mut self,
+        attacker: &player::Player,
+        idx: usize,
+        dice: &DiceExpr,
+        effects: &[StatusEffect],
+        events: &mut Vec<GameEvent>,
+        rng: &mut R,
+    ) -> TurnOutcome {
+        let sight = self.arena.line_of_sight(&attacker.loc, &self.players[idx].loc, &self.bystanders(attacker, idx));
+        let opponent = &mut self.players[idx];
+        if sight == Sight::Blocked {
+            events.push(GameEvent::AttackBlocked {
+                attacker: attacker.name.clone(),
+                target: opponent.name.clone(),
+            });
+            return TurnOutcome::Missed {
+                attacker: attacker.name.clone(),
+                target: opponent.name.clone(),
+            };
+        }
+        let roll = attacker.attack(opponent, &self.rules, sight.cover(), rng);
+        events.push(GameEvent::AttackRolled {
+            attacker: attacker.name.clone(),
+            target: opponent.name.clone(),
+            roll: roll.roll,
+            natural: roll.natural,
+            bonus: roll.bonus,
+            target_defense: roll.target_defense,
+            cover: roll.cover,
+            critical: roll.critical,
+            fumble: roll.fumble,
+        });
+        if !roll.is_hit() {
+            return TurnOutcome::Missed {
+                attacker: attacker.name.clone(),
+                target: opponent.name.clone(),
+            };
+        }
+        let critical = roll.critical.then_some(self.rules.crit_damage);
+        let damage_done = attacker.damage(opponent, dice, critical, rng);
+        let target = opponent.name.clone();
+        events.push(GameEvent::Damaged {
+            who: target.clone(),
+            by: attacker.name.clone(),
+            amount: damage_done,
+            remaining: opponent.armor.curr,
+            critical: roll.critical,
+        });
+        let defeated = opponent.is_dead();
+        if defeated {
+            events.push(GameEvent::Defeated {
+                who: target.clone(),
+                by: attacker.name.clone(),
+            });
+            let side = opponent.side().to_string();
+            self.allies_lose_heart(&target, &side, events, rng);
+        } else {
+            if opponent.armor.curr * 2 <= opponent.armor.base {
+                Self::test_morale(&self.rules, opponent, events, rng);
+            }
+            for effect in effects {
+                events.push(GameEvent::EffectApplied {
+                    who: target.clone(),
+                    by: attacker.name.clone(),
+                    condition: effect.condition,
+                    turns: effect.turns,
+                    potency: effect.potency,
+                });
+                opponent.add_effect(StatusEffect::new(
+                    effect.condition,
+                    effect.turns,
+                    effect.potency,
+                    &attacker.name,
+                ));
+            }
+        }
+        TurnOutcome::Hit {
+            attacker: attacker.name.clone(),
+            target,
+            damage: damage_done,
+            critical: roll.critical,
+            defeated,
         }
     }
-    pub fn get_nearest(&mut self, source: &player::Player) -> Option<(usize, &mut player::Player)> {
-        let mut min_distance = f32::MAX;
-        let mut target = None;
-        for (idx, player) in self.players.iter_mut().enumerate() {
-            if source.name != player.name {
-                let distance = source.loc.distance(&player.loc);
-                if distance < min_distance {
-                    min_distance = distance;
-                    target = Some((idx, player));
-                }
-            }
-        }
-        target
-    }
-    pub fn run_simulation(&mut self, rng: &mut ThreadRng) -> i32 {
-        while self.players.len() > 1 {
-            let mut player = self.players.pop_front().unwrap();
-            let (idx, nearest_player) = self.get_nearest(&player).unwrap();
-            if player.in_range(&nearest_player.loc) {
-                info!("{} is in range of {}", player.name, nearest_player.name);
-                if player.attack(nearest_player, rng) {
-                    let damage_done = player.damage(nearest_player, rng);
-                    info!(
-                        "{} hit {} for {} damage",
-                        player.name, nearest_player.name, damage_done
-                    );
-                    if nearest_player.is_dead() {
-                        warn!("{} defeated {}", player.name, nearest_player.name);
-                        drop(self.players.remove(idx));
+    /// Use the player's ability in slot `ability`, aimed at the player at `idx` unless
+    /// the ability picks its own target.
+    fn use_ability<R: Rng>(
+        &mut self,
+        player: &mut player::Player,
+        ability: usize,
+        idx: usize,
+        events: &mut Vec<GameEvent>,
+        rng: &mut R,
+    ) -> TurnOutcome {
+        let slot = &mut player.abilities[ability];
+        // cooldowns tick down at the end of every turn, this one included
+        slot.cooldown = slot.ability.cooldown + 1;
+        let name = slot.name.clone();
+        let ability = slot.ability.clone();
+        let mut target = self.players[idx].name.clone();
+        let announce = events.len();
+
+        match &ability.kind {
+            AbilityKind::Area { radius, damage } => {
+                let center = self.players[idx].loc.clone();
+                let hit: Vec<usize> = (0..self.players.len())
+                    .filter(|&i| {
+                        let p = &self.players[i];
+                        !player.is_ally(p) && !p.is_dead() && p.loc.distance(&center) <= *radius
+                    })
+                    .collect();
+                for i in hit {
+                    self.strike(player, i, damage, &ability.effects, events, rng);
+                }
+            }
+            AbilityKind::Heal { amount } => {
+                let reach = ability.reach(player);
+                let ally = abilities::heal_target(player, reach, &self.players);
+                let deficit = |p: &player::Player| p.armor.base - p.armor.curr;
+                let healer = player.name.clone();
+                let patient = match ally {
+                    Some(i) if deficit(&self.players[i]) > deficit(player) => &mut self.players[i],
+                    _ => &mut *player,
+                };
+                let restored = patient.heal(amount.roll(rng).total);
+                target = patient.name.clone();
+                events.push(GameEvent::Healed {
+                    who: target.clone(),
+                    by: healer,
+                    amount: restored,
+                    remaining: patient.armor.curr,
+                });
+            }
+            AbilityKind::Charge { damage } => {
+                let goal = self.players[idx].loc.clone();
+                let gap = player.loc.distance(&goal) - player.effective_range() as f32;
+                let from = player.loc.clone();
+                let rush = gap.clamp(0.0, ability.reach(player));
+                let fall = player.rush_towards(&goal, rush, &self.arena, &self.crowd(player));
+                self.report_move(player, from, fall, events, rng);
+                if !player.is_dead() && player.in_range(&self.players[idx], &self.arena) {
+                    self.strike(player, idx, damage, &ability.effects, events, rng);
+                }
+            }
+            AbilityKind::Shield { amount, turns } => {
+                let potency = amount.roll(rng).total.max(1);
+                target = player.name.clone();
+                events.push(GameEvent::EffectApplied {
+                    who: player.name.clone(),
+                    by: player.name.clone(),
+                    condition: Condition::Shielded,
+                    turns: *turns,
+                    potency,
+                });
+                player.add_effect(StatusEffect::new(Condition::Shielded, *turns, potency, &player.name));
+            }
+            AbilityKind::Volley { shots } => {
+                let dice = player.damage_dice(&self.rules).clone();
+                for _ in 0..*shots {
+                    if self.players[idx].is_dead() {
+                        break;
                     }
+                    self.strike(player, idx, &dice, &ability.effects, events, rng);
+                }
+            }
+        }
+        events.insert(
+            announce,
+            GameEvent::AbilityUsed {
+                who: player.name.clone(),
+                ability: name.clone(),
+                target: target.clone(),
+            },
+        );
+        TurnOutcome::UsedAbility {
+            player: player.name.clone(),
+            ability: name,
+            target,
+        }
+    }
+    /// Spend a charge of the player's consumable in slot `item` on itself.
+    fn use_consumable<R: Rng>(
+        player: &mut player::Player,
+        item: usize,
+        events: &mut Vec<GameEvent>,
+        rng: &mut R,
+    ) -> TurnOutcome {
+        let slot = &mut player.equipment.consumables[item];
+        slot.charges -= 1;
+        let name = slot.name.clone();
+        let charges_left = slot.charges;
+        let amount = slot.item.heal.roll(rng).total;
+        let restored = player.heal(amount);
+        events.push(GameEvent::ItemUsed {
+            who: player.name.clone(),
+            item: name.clone(),
+            charges_left,
+        });
+        events.push(GameEvent::Healed {
+            who: player.name.clone(),
+            by: player.name.clone(),
+            amount: restored,
+            remaining: player.armor.curr,
+        });
+        TurnOutcome::UsedItem {
+            player: player.name.clone(),
+            item: name,
+        }
+    }
+    /// A routed player runs from the nearest enemy and leaves the battle once it is
+    /// far enough from all of them or reaches the edge of the arena.
+    fn flee<R: Rng>(
+        &mut self,
+        player: &mut player::Player,
+        idx: usize,
+        events: &mut Vec<GameEvent>,
+        rng: &mut R,
+    ) -> TurnOutcome {
+        let target = self.players[idx].name.clone();
+        let threat = self.players[idx].loc.clone();
+        let distance = player.speed.curr as f32;
+        let from = player.loc.clone();
+        let fall = player.move_away(&threat, distance, &self.arena, &self.crowd(player));
+        self.report_move(player, from, fall, events, rng);
+        let outcome = TurnOutcome::Retreated {
+            player: player.name.clone(),
+            target,
+            distance,
+        };
+        if player.is_dead() {
+            return outcome;
+        }
+        let safe = self.arena.at_edge(&player.loc)
+            || self
+                .players
+                .iter()
+                .filter(|p| !player.is_ally(p))
+                .all(|p| p.loc.distance(&player.loc) >= self.rules.escape_distance);
+        if safe {
+            player.morale_state = MoraleState::Escaped;
+            events.push(GameEvent::Escaped {
+                who: player.name.clone(),
+            });
+        }
+        outcome
+    }
+    /// Test `player`'s nerve, updating its morale state and reporting the result.
+    fn test_morale<R: Rng>(rules: &Rules, player: &mut player::Player, events: &mut Vec<GameEvent>, rng: &mut R) {
+        let Some(check) = morale::check(player, rules, rng) else {
+            return;
+        };
+        events.push(GameEvent::MoraleChecked {
+            who: player.name.clone(),
+            roll: check.roll,
+            morale: check.morale,
+            passed: check.passed(),
+        });
+        player.morale_state = check.outcome;
+        match check.outcome {
+            MoraleState::Routed => events.push(GameEvent::Routed {
+                who: player.name.clone(),
+            }),
+            MoraleState::Surrendered => events.push(GameEvent::Surrendered {
+                who: player.name.clone(),
+            }),
+            MoraleState::Steady | MoraleState::Escaped => {}
+        }
+    }
+    /// Every ally still standing tests its morale when `fallen` of `side` goes down.
+    fn allies_lose_heart<R: Rng>(&mut self, fallen: &str, side: &str, events: &mut Vec<GameEvent>, rng: &mut R) {
+        for ally in self.players.iter_mut() {
+            if ally.side() == side && ally.name != fallen && !ally.is_dead() {
+                Self::test_morale(&self.rules, ally, events, rng);
+            }
+        }
+    }
+    /// Move the players defeated this turn over to `fallen` and those that surrendered
+    /// or escaped over to `withdrawn`, in roster order.
+    fn clear_field(&mut self) {
+        let leaving = |p: &player::Player| p.is_dead() || p.morale_state.is_withdrawn();
+        if self.players.iter().any(leaving) {
+            let (gone, staying): (Vec<_>, Vec<_>) =
+                std::mem::take(&mut self.players).into_iter().partition(leaving);
+            self.players = staying.into();
+            for player in gone {
+                if player.is_dead() {
+                    self.fallen.push(player);
                 } else {
-                    info!("{} missed", player.name);
+                    self.withdrawn.push(player);
+                }
+            }
+        }
+    }
+    /// Run the acting player's effects and regeneration for its turn, returning who gets
+    /// the credit if the effects finished it off.
+    fn tick_effects(player: &mut player::Player, events: &mut Vec<GameEvent>) -> Option<String> {
+        let (hurting, expired) = player.tick_effects();
+        let mut remaining = player.armor.curr + hurting.iter().map(|e| e.potency).sum::<i32>();
+        for effect in &hurting {
+            remaining -= effect.potency;
+            events.push(GameEvent::EffectTicked {
+                who: player.name.clone(),
+                condition: effect.condition,
+                damage: effect.potency,
+                remaining,
+            });
+        }
+        for effect in expired {
+            events.push(GameEvent::EffectExpired {
+                who: player.name.clone(),
+                condition: effect.condition,
+            });
+        }
+        if player.is_dead() {
+            return hurting.last().map(|effect| effect.source.clone());
+        }
+        if player.regen.curr > 0 && player.armor.curr < player.armor.base {
+            let restored = player.heal(player.regen.curr);
+            events.push(GameEvent::Healed {
+                who: player.name.clone(),
+                by: player.name.clone(),
+                amount: restored,
+                remaining: player.armor.curr,
+            });
+        }
+        None
+    }
+    fn finish_turn(&mut self, events: Vec<GameEvent>, outcome: TurnOutcome) -> TurnOutcome {
+        for event in events {
+            self.emit(event);
+        }
+        if self.status() == BattleStatus::TurnLimitReached {
+            self.turns = MAX_TURNS;
+            self.emit(GameEvent::TurnLimitReached { turns: self.turns });
+        }
+        outcome
+    }
+    /// Apply a recorded event to the roster instead of rolling for it, then pass it on
+    /// to the observers as if it had just happened.
+    pub fn apply(&mut self, turn: i32, event: GameEvent) {
+        self.turns = turn;
+        match &event {
+            GameEvent::TurnStarted { who } => {
+                if let Some(player) = self.players.iter_mut().find(|p| &p.name == who) {
+                    // the damage and expiries follow as events of their own
+                    player.tick_effects();
+                    player.tick_cooldowns();
                 }
-            } else {
-                let distance = player.loc.distance(&nearest_player.loc);
-                info!(
-                    "{} is moving towards {} at a distance of {}",
-                    player.name, nearest_player.name, distance
-                );
-                player.move_towards(&nearest_player.loc);
             }
-            self.players.push_back(player);
-            self.turns += 1;
-            if self.turns > MAX_TURNS {
-                warn!("Battle is taking too many turns: {}", self.turns);
-                break;
+            GameEvent::Moved { who, to, route, .. } => {
+                if let Some(player) = self.players.iter_mut().find(|p| &p.name == who) {
+                    player.loc = to.clone();
+                    player.route = route.clone();
+                }
             }
+            GameEvent::Damaged { who, remaining, .. } => {
+                if let Some(player) = self.players.iter_mut().find(|p| &p.name == who) {
+                    player.armor.curr = *remaining;
+                }
+            }
+            GameEvent::Defeated { who, .. } => {
+                if let Some(idx) = self.players.iter().position(|p| &p.name == who) {
+                    self.fallen.extend(self.players.remove(idx));
+                }
+            }
+            GameEvent::EffectApplied { who, by, condition, turns, potency } => {
+                if let Some(player) = self.players.iter_mut().find(|p| &p.name == who) {
+                    player.add_effect(StatusEffect::new(*condition, *turns, *potency, by));
+                }
+            }
+            GameEvent::Routed { who } => {
+                if let Some(player) = self.players.iter_mut().find(|p| &p.name == who) {
+                    player.morale_state = MoraleState::Routed;
+                }
+            }
+            GameEvent::Surrendered { who } | GameEvent::Escaped { who } => {
+                if let Some(idx) = self.players.iter().position(|p| &p.name == who) {
+                    self.players[idx].morale_state = match &event {
+                        GameEvent::Surrendered { .. } => MoraleState::Surrendered,
+                        _ => MoraleState::Escaped,
+                    };
+                    self.withdrawn.extend(self.players.remove(idx));
+                }
+            }
+            GameEvent::ItemUsed { who, item, charges_left } => {
+                if let Some(player) = self.players.iter_mut().find(|p| &p.name == who) {
+                    for slot in player.equipment.consumables.iter_mut().filter(|s| &s.name == item) {
+                        slot.charges = *charges_left;
+                    }
+                }
+            }
+            GameEvent::AbilityUsed { who, ability, .. } => {
+                if let Some(player) = self.players.iter_mut().find(|p| &p.name == who) {
+                    // `TurnStarted` already ticked the cooldowns `step` ticks at the end of the turn
+                    for slot in player.abilities.iter_mut().filter(|s| &s.name == ability) {
+                        slot.cooldown = slot.ability.cooldown;
+                    }
+                }
+            }
+            GameEvent::EffectTicked { who, remaining, .. }
+            | GameEvent::Healed { who, remaining, .. }
+            | GameEvent::Fell { who, remaining, .. } => {
+                if let Some(player) = self.players.iter_mut().find(|p| &p.name == who) {
+                    player.armor.curr = *remaining;
+                }
+            }
+            GameEvent::EffectExpired { who, condition } => {
+                if let Some(player) = self.players.iter_mut().find(|p| &p.name == who) {
+                    player.remove_effect(*condition);
+                }
+            }
+            GameEvent::AttackRolled { .. }
+            | GameEvent::AttackBlocked { .. }
+            | GameEvent::RoundStarted { .. }
+            | GameEvent::Stunned { .. }
+            | GameEvent::MoraleChecked { .. }
+            | GameEvent::Held { .. }
+            | GameEvent::TurnLimitReached { .. } => {}
+        }
+        self.emit(event);
+    }
+    /// Current and base armor summed over the players still contending.
+    pub fn remaining_armor(&self) -> (i32, i32) {
+        self.contenders()
+            .into_iter()
+            .fold((0, 0), |(curr, base), p| (curr + p.armor.curr, base + p.armor.base))
+    }
+    pub fn run_simulation<R: Rng>(&mut self, rng: &mut R) -> i32 {
+        while self.status() == BattleStatus::Ongoing {
+            self.step(rng);
         }
         self.turns
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventLog;
+    use crate::player::Player;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_step_moves_out_of_range_player() {
+        let mut game = Game::new();
+        game.players.push_back(Player::fighter("A", 0.0));
+        let mut b = Player::fighter("B", 20.0);
+        b.ready_at = 1;
+        game.players.push_back(b);
+        let mut rng = crate::dice::seeded_rng(1);
+
+        let outcome = game.step(&mut rng);
+
+        assert!(matches!(outcome, TurnOutcome::Moved { ref player, .. } if player == "A"));
+        assert_eq!(game.turns, 1);
+        assert_eq!(game.players[0].loc.x, 5.0);
+        assert_eq!(game.players[0].ready_at, TICKS_PER_ROUND * BASE_SPEED / 5);
+    }
+
+    #[test]
+    fn test_players_stop_up_against_their_target() {
+        let mut game = Game::new();
+        let mut a = Player::fighter("A", 0.0);
+        a.range.set(0);
+        game.players.push_back(a);
+        let mut b = Player::fighter("B", 4.0);
+        b.ready_at = 1;
+        game.players.push_back(b);
+        let mut rng = crate::dice::seeded_rng(3);
+
+        game.step(&mut rng);
+
+        let (a, b) = (&game.players[0], &game.players[1]);
+        assert!((a.loc.distance(&b.loc) - (a.size + b.size)).abs() < 0.01);
+        assert!(a.in_range(b, &game.arena));
+    }
+
+    #[test]
+    fn test_faster_players_act_more_often() {
+        let mut game = Game::new();
+        game.arena = Arena::new(600.0, 10.0);
+        let mut fast = Player::fighter("fast", 0.0);
+        fast.speed.set(20);
+        fast.range.set(0);
+        let mut slow = Player::fighter("slow", 500.0);
+        slow.speed.set(10);
+        game.players.push_back(fast);
+        game.players.push_back(slow);
+        let mut rng = crate::dice::seeded_rng(3);
+
+        let mut acted = HashMap::new();
+        while game.next_round() <= 4 {
+            if let TurnOutcome::Moved { player, .. } = game.step(&mut rng) {
+                *acted.entry(player).or_insert(0) += 1;
+            }
+        }
+
+        assert_eq!(acted["fast"], 8);
+        assert_eq!(acted["slow"], 4);
+    }
+
+    #[test]
+    fn test_simulation_stops_at_turn_limit() {
+        let mut game = Game::new();
+        let mut a = Player::fighter("A", 0.0);
+        let mut b = Player::fighter("B", 20.0);
+        a.speed.set(0);
+        b.speed.set(0);
+        game.players.push_back(a);
+        game.players.push_back(b);
+        let mut rng = crate::dice::seeded_rng(1);
+
+        assert_eq!(game.run_simulation(&mut rng), MAX_TURNS);
+        assert_eq!(game.status(), BattleStatus::TurnLimitReached);
+        assert_eq!(game.step(&mut rng), TurnOutcome::Over);
+    }
+
+    #[test]
+    fn test_step_emits_events_to_observers() {
+        let mut game = Game::new();
+        let log = Rc::new(RefCell::new(EventLog::default()));
+        game.subscribe(Box::new(log.clone()));
+        game.players.push_back(Player::fighter("A", 0.0));
+        game.players.push_back(Player::fighter("B", 1.0));
+        let mut rng = crate::dice::seeded_rng(1);
+
+        game.step(&mut rng);
+
+        let events = log.borrow_mut().drain();
+        assert!(matches!(events[0], (1, GameEvent::RoundStarted { round: 1, .. })));
+        assert!(matches!(events[1].1, GameEvent::TurnStarted { ref who } if who == "A"));
+        let (turn, attack) = &events[2];
+        assert_eq!(*turn, 1);
+        let GameEvent::AttackRolled { attacker, target, .. } = attack else {
+            panic!("expected an attack, got {:?}", attack);
+        };
+        assert!(attack.is_hit());
+        assert!(matches!(events[3].1, GameEvent::Damaged { ref who, .. } if who == target));
+        assert_ne!(attacker, target);
+    }
+
+    #[test]
+    fn test_same_seed_replays_same_battle() {
+        let play = |seed| {
+            let mut game = Game::new();
+            let log = Rc::new(RefCell::new(EventLog::default()));
+            game.subscribe(Box::new(log.clone()));
+            let mut rng = crate::dice::seeded_rng(seed);
+            for name in ["A", "B", "C"] {
+                let mut player = Player::new(name);
+                player.randomize(&game.rules, &game.arena, &mut rng);
+                game.players.push_back(player);
+            }
+            game.run_simulation(&mut rng);
+            log.borrow_mut().drain()
+        };
+
+        assert_eq!(play(7), play(7));
+    }
+
+    #[test]
+    fn test_allies_are_not_targeted() {
+        let mut game = Game::new();
+        let mut a = Player::fighter("A", 0.0);
+        let mut b = Player::fighter("B", 1.0);
+        let c = Player::fighter("C", 10.0);
+        a.team = Some("red".to_string());
+        b.team = Some("red".to_string());
+        game.players.push_back(a);
+        game.players.push_back(b);
+        game.players.push_back(c);
+
+        let source = game.players.pop_front().unwrap();
+        let mut rng = crate::dice::seeded_rng(1);
+        let idx = game.select_target(&source, &mut rng).unwrap();
+        assert_eq!(game.players[idx].name, "C");
+
+        game.players.pop_back();
+        game.players.push_back(source);
+        assert_eq!(game.status(), BattleStatus::Victory);
+        assert_eq!(game.winning_side(), Some("red"));
+    }
+
+    #[test]
+    fn test_critical_range_and_fumbles() {
+        let mut game = Game::new();
+        game.rules.crit_range = 2;
+        let log = Rc::new(RefCell::new(EventLog::default()));
+        game.subscribe(Box::new(log.clone()));
+        let mut a = Player::fighter("A", 0.0);
+        let mut b = Player::fighter("B", 1.0);
+        // nothing but a critical can get past this defense
+        a.defense.set(40);
+        b.defense.set(40);
+        a.armor.set(1000);
+        b.armor.set(1000);
+        game.players.push_back(a);
+        game.players.push_back(b);
+        let mut rng = crate::dice::seeded_rng(4);
+
+        for _ in 0..40 {
+            game.step(&mut rng);
+        }
+
+        let attacks: Vec<GameEvent> = log
+            .borrow_mut()
+            .drain()
+            .into_iter()
+            .map(|(_, event)| event)
+            .filter(|event| matches!(event, GameEvent::AttackRolled { .. }))
+            .collect();
+        assert_eq!(attacks.len(), 40);
+        for attack in attacks {
+            let GameEvent::AttackRolled { natural, critical, fumble, .. } = attack else { unreachable!() };
+            assert_eq!(fumble, natural == 1);
+            assert_eq!(critical, natural >= 2);
+            assert_eq!(attack.is_hit(), critical);
+        }
+    }
+
+    #[test]
+    fn test_stun_skips_turn_and_poison_finishes_off() {
+        let mut game = Game::new();
+        let mut a = Player::fighter("A", 0.0);
+        a.add_effect(StatusEffect::new(Condition::Stunned, 1, 1, "B"));
+        a.add_effect(StatusEffect::new(Condition::Slowed, 2, 3, "B"));
+        let mut b = Player::fighter("B", 20.0);
+        b.armor.curr = 2;
+        b.add_effect(StatusEffect::new(Condition::Poisoned, 3, 2, "A"));
+        b.ready_at = 1;
+        game.players.push_back(a);
+        game.players.push_back(b);
+        let mut rng = crate::dice::seeded_rng(1);
+
+        assert_eq!(game.players[0].speed.curr, 2);
+        assert_eq!(game.step(&mut rng), TurnOutcome::Stunned { player: "A".to_string() });
+        assert!(!game.players[0].has_condition(Condition::Stunned));
+        assert_eq!(game.step(&mut rng), TurnOutcome::Succumbed { player: "B".to_string() });
+        assert_eq!(game.fallen[0].name, "B");
+        assert_eq!(game.winning_side(), Some("A"));
+    }
+
+    #[test]
+    fn test_ready_ability_replaces_basic_attack() {
+        let mut game = Game::new();
+        let log = Rc::new(RefCell::new(EventLog::default()));
+        game.subscribe(Box::new(log.clone()));
+        let volley: abilities::AbilityCatalog =
+            serde_yaml::from_str("volley:\n  kind: volley\n  shots: 3\n  cooldown: 2\n").unwrap();
+        let mut a = Player::fighter("A", 0.0);
+        a.abilities = abilities::learn(&volley, &["volley".to_string()]).unwrap();
+        let mut b = Player::fighter("B", 1.0);
+        b.armor.set(1000);
+        b.ready_at = 1;
+        game.players.push_back(a);
+        game.players.push_back(b);
+        let mut rng = crate::dice::seeded_rng(2);
+
+        let outcome = game.step(&mut rng);
+
+        assert!(matches!(outcome, TurnOutcome::UsedAbility { ref ability, .. } if ability == "volley"));
+        assert_eq!(game.players[0].abilities[0].cooldown, 2);
+        let events = log.borrow_mut().drain();
+        assert!(matches!(events[2].1, GameEvent::AbilityUsed { .. }));
+        let shots = events.iter().filter(|(_, e)| matches!(e, GameEvent::AttackRolled { .. })).count();
+        assert_eq!(shots, 3);
+    }
+
+    #[test]
+    fn test_abilities_wait_out_their_cooldown() {
+        let mut game = Game::new();
+        let volley: abilities::AbilityCatalog =
+            serde_yaml::from_str("volley:\n  kind: volley\n  shots: 1\n  cooldown: 2\n").unwrap();
+        let mut a = Player::fighter("A", 0.0);
+        a.abilities = abilities::learn(&volley, &["volley".to_string()]).unwrap();
+        a.armor.set(1000);
+        let mut b = Player::fighter("B", 1.0);
+        b.armor.set(1000);
+        game.players.push_back(a);
+        game.players.push_back(b);
+        let mut rng = crate::dice::seeded_rng(2);
+
+        let mut uses = Vec::new();
+        let mut turns_of_a = 0;
+        while turns_of_a < 9 {
+            match game.step(&mut rng) {
+                TurnOutcome::UsedAbility { player, .. } if player == "A" => uses.push(turns_of_a),
+                TurnOutcome::Hit { attacker, .. } | TurnOutcome::Missed { attacker, .. } if attacker == "A" => {}
+                _ => continue,
+            }
+            turns_of_a += 1;
+        }
+
+        // two of A's turns go by between uses
+        assert_eq!(uses, [0, 3, 6]);
+    }
+
+    #[test]
+    fn test_regeneration_and_potions() {
+        let mut game = Game::new();
+        let catalog: crate::equipment::ItemCatalog =
+            serde_yaml::from_str("consumables:\n  potion:\n    heal: '5'\n    charges: 1\n").unwrap();
+        let config = crate::equipment::EquipmentConfig {
+            consumables: vec!["potion".to_string()],
+            ..Default::default()
+        };
+        let mut a = Player::fighter("A", 0.0);
+        a.equipment = catalog.equip(&config).unwrap();
+        a.armor.curr = 4;
+        a.regen.set(1);
+        let mut b = Player::fighter("B", 1.0);
+        b.ready_at = 1;
+        game.players.push_back(a);
+        game.players.push_back(b);
+        let mut rng = crate::dice::seeded_rng(2);
+
+        let outcome = game.step(&mut rng);
+
+        assert_eq!(outcome, TurnOutcome::UsedItem { player: "A".to_string(), item: "potion".to_string() });
+        assert_eq!(game.players[0].armor.curr, 10);
+        assert_eq!(game.players[0].consumable_to_use(), None);
+        game.players[0].armor.curr = 2;
+        assert_eq!(game.players[0].consumable_to_use(), None);
+    }
+
+    #[test]
+    fn test_routed_players_flee_and_do_not_hold_the_field() {
+        let mut game = Game::new();
+        game.rules.escape_distance = 4.0;
+        let mut a = Player::fighter("A", 0.0);
+        a.ready_at = 1;
+        let mut b = Player::fighter("B", 1.0);
+        b.morale_state = MoraleState::Routed;
+        let c = Player::fighter("C", 2.0);
+        game.players.push_back(a);
+        game.players.push_back(b);
+        game.players.push_back(c);
+
+        assert_eq!(game.status(), BattleStatus::Ongoing);
+        game.players.pop_back();
+        assert_eq!(game.winning_side(), Some("A"));
+
+        game.players.push_back(Player::fighter("C", 2.0));
+        game.players[2].ready_at = 1;
+        let mut rng = crate::dice::seeded_rng(1);
+        assert!(matches!(game.step(&mut rng), TurnOutcome::Retreated { ref player, .. } if player == "B"));
+        assert_eq!(game.players.len(), 2);
+        assert_eq!(game.withdrawn[0].morale_state, MoraleState::Escaped);
+    }
+
+    #[test]
+    fn test_cover_shows_in_attack_events() {
+        let mut game = Game::new();
+        game.arena.cover.push(crate::arena::Cover {
+            region: crate::arena::Region { x: 0.5, y: 0.0, width: 2.0, height: 2.0 },
+            defense: 2,
+        });
+        let log = Rc::new(RefCell::new(EventLog::default()));
+        game.subscribe(Box::new(log.clone()));
+        game.players.push_back(Player::fighter("A", 0.0));
+        let mut b = Player::fighter("B", 1.0);
+        b.ready_at = 1;
+        game.players.push_back(b);
+        let mut rng = crate::dice::seeded_rng(5);
+
+        game.step(&mut rng);
+
+        let events = log.borrow_mut().drain();
+        let GameEvent::AttackRolled { cover, target_defense, .. } = events[2].1 else {
+            panic!("expected an attack, got {:?}", events[2].1);
+        };
+        assert_eq!((cover, target_defense), (2, 12));
+    }
+
+    #[test]
+    fn test_walking_off_a_ledge_hurts() {
+        let mut game = Game::new();
+        game.arena.elevation.push(crate::arena::Elevation {
+            region: crate::arena::Region { x: 0.0, y: 0.0, width: 3.0, height: 60.0 },
+            level: 8.0,
+        });
+        let log = Rc::new(RefCell::new(EventLog::default()));
+        game.subscribe(Box::new(log.clone()));
+        let mut a = Player::fighter("A", 1.0);
+        a.loc.y = 10.0;
+        a.loc.z = 8.0;
+        game.players.push_back(a);
+        let mut b = Player::fighter("B", 12.0);
+        b.loc.y = 10.0;
+        b.ready_at = 1;
+        game.players.push_back(b);
+        let mut rng = crate::dice::seeded_rng(5);
+
+        game.step(&mut rng);
+
+        let events = log.borrow_mut().drain();
+        let GameEvent::Fell { height, damage, remaining, .. } = events[3].1 else {
+            panic!("expected a fall, got {:?}", events[3].1);
+        };
+        assert_eq!(height, 8.0);
+        assert!((2..=12).contains(&damage));
+        assert_eq!(remaining, 10 - damage);
+        assert_eq!(game.players.back().unwrap().loc.z, 0.0);
+    }
+}
@@ -1,12 +1,31 @@
-use rand::rngs::ThreadRng;
+use rand::Rng;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io;
+use std::process;
+use std::rc::Rc;
 use tracing::{error, info};
-use clap::Parser;
+use clap::{Parser, Subcommand};
+mod abilities;
+mod arena;
 mod dice;
+mod effects;
+mod equipment;
+mod events;
 mod game;
+mod morale;
 mod names;
+mod pathfinding;
 mod player;
+mod replay;
+mod report;
+mod rules;
 mod serialization;
+mod targeting;
+mod validation;
 mod app;
+mod behavior;
+mod batch;
 mod tui;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -18,6 +37,8 @@ const MAX_PLAYERS: u8 = 64;
 #[command(version = "2.0.0")]
 #[command(about = "Simulation of a skirmish", long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Number of random players to simulate
     #[arg(short, long, default_value_t = 2)]
     players: u8,
@@ -25,24 +46,94 @@ struct Args {
     #[arg(short, long)]
     config: Option<String>,
     /// Enable TUI mode for interactive battle visualization
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     tui: bool,
+    /// Seed for the random number generator, overrides the seed in the configuration
+    #[arg(short, long)]
+    seed: Option<u64>,
+    /// Record the battle to a replay file
+    #[arg(short, long)]
+    record: Option<String>,
+    /// Number of battles to run with the same roster, reporting win rates
+    #[arg(long, default_value_t = 1, conflicts_with_all = ["tui", "record", "output", "output_file"])]
+    runs: u32,
+    /// Number of threads used to run batches
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+    /// Write the battle result in a machine-readable format
+    #[arg(short, long, value_enum)]
+    output: Option<report::OutputFormat>,
+    /// File to write the result to instead of stdout
+    #[arg(long, requires = "output")]
+    output_file: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Play back a recorded battle without rolling any dice
+    Replay {
+        /// Path to the replay file
+        file: String,
+    },
 }
 
 fn main() {
     // get the command arguments
     let args = Args::parse();
     
-    if args.tui {
+    match (&args.command, args.tui) {
+        (Some(Command::Replay { file }), true) => run_tui_replay(file),
+        (Some(Command::Replay { file }), false) => run_cli_replay(file),
         // Run in TUI mode
-        run_tui_mode(args);
-    } else {
+        (None, true) => run_tui_mode(args),
+        // Run many battles and report statistics
+        (None, false) if args.runs > 1 => run_batch_mode(args),
         // Run in CLI mode
-        run_cli_mode(args);
+        (None, false) => run_cli_mode(args),
     }
 }
 
 fn run_tui_mode(args: Args) {
+    // Load players before the terminal is taken over so errors stay readable
+    let config = load_config(&args);
+    let seed = pick_seed(&args, config.as_ref());
+    let mut rng = dice::seeded_rng(seed);
+    let rules = config.as_ref().map(|config| config.rules.clone()).unwrap_or_default();
+    let arena = config.as_ref().map(|config| config.arena.clone()).unwrap_or_default();
+    let players = load_players(&args, config, &mut rng);
+
+    // Create app and add players
+    let mut app = app::App::new();
+    app.seed = Some(seed);
+    app.game.rules = rules;
+    app.game.arena = arena;
+    app.add_players(players);
+    let recorder = start_recording(&args, seed, &mut app.game);
+
+    run_tui(app, &mut rng);
+    finish_recording(&args, recorder);
+}
+
+fn run_tui_replay(file: &str) {
+    let replay = match replay::Replay::load(file) {
+        Ok(replay) => replay,
+        Err(e) => {
+            eprintln!("Failed to load replay from {}: {}", file, e);
+            return;
+        }
+    };
+
+    let mut app = app::App::new();
+    app.seed = Some(replay.seed);
+    app.game.arena = replay.arena.clone();
+    app.add_players(replay.players());
+    app.playback = Some(replay::Playback::new(&replay));
+
+    // the dice are never rolled during playback
+    run_tui(app, &mut dice::seeded_rng(replay.seed));
+}
+
+fn run_tui<R: Rng>(app: app::App, rng: &mut R) {
     // Initialize terminal
     let terminal = match tui::setup_terminal() {
         Ok(terminal) => terminal,
@@ -52,14 +143,9 @@ fn run_tui_mode(args: Args) {
         }
     };
     
-    // Create app and load players
-    let mut app = app::App::new();
-    let players = load_players(args);
-    app.add_players(players);
-    
     // Create TUI and run
     let mut tui_instance = tui::Tui::new(terminal);
-    if let Err(e) = tui_instance.run(app) {
+    if let Err(e) = tui_instance.run(app, rng) {
         eprintln!("TUI error: {}", e);
     }
     
@@ -70,67 +156,182 @@ fn run_tui_mode(args: Args) {
 }
 
 fn run_cli_mode(args: Args) {
-    // turn on logging
-    tracing_subscriber::fmt::init();
+    // turn on logging, keeping stdout clean when it carries the result
+    if args.output.is_some() {
+        tracing_subscriber::fmt().with_writer(io::stderr).init();
+    } else {
+        tracing_subscriber::fmt::init();
+    }
     info!("battlers/{}", VERSION);
 
     // initialize the random number generator
-    let mut rng: ThreadRng = rand::rng();
+    let config = load_config(&args);
+    let seed = pick_seed(&args, config.as_ref());
+    info!("using seed {}", seed);
+    let mut rng = dice::seeded_rng(seed);
 
     // create a new game engine and add players
     let mut game = game::Game::new();
-    let players = load_players(args);
+    game.subscribe(Box::new(events::LogObserver));
+    if let Some(config) = &config {
+        game.rules = config.rules.clone();
+        game.arena = config.arena.clone();
+    }
+    let players = load_players(&args, config, &mut rng);
     
     for player in players {
         info!("{:?}", player);
         game.players.push_back(player);
     }
+    let recorder = start_recording(&args, seed, &mut game);
     
     info!("{} players enter the skirmish", game.players.len());
 
     // run the simulation with the players
-    let turns_elapsed = game.run_simulation(&mut rng);
-    if game.players.len() == 1 {
+    let rounds_elapsed = game.run_simulation(&mut rng);
+    report_winner(&game, rounds_elapsed);
+    finish_recording(&args, recorder);
+    if let Some(format) = args.output {
+        write_result(&report::BattleResult::new(&game, seed), format, args.output_file.as_deref());
+    }
+}
+
+fn write_result(result: &report::BattleResult, format: report::OutputFormat, path: Option<&str>) {
+    let written = match path {
+        Some(path) => File::create(path)
+            .map_err(|e| e.into())
+            .and_then(|mut file| result.write(format, &mut file)),
+        None => result.write(format, &mut io::stdout().lock()),
+    };
+    if let Err(e) = written {
+        error!("Failed to write result to {}: {}", path.unwrap_or("stdout"), e);
+    }
+}
+
+fn run_batch_mode(args: Args) {
+    // per-battle logging would drown out the report
+    tracing_subscriber::fmt().with_max_level(tracing::Level::ERROR).init();
+
+    let config = load_config(&args);
+    let seed = pick_seed(&args, config.as_ref());
+    let config = match config {
+        Some(mut config) => {
+            // every run fights with the same roster, so whatever it leaves out is rolled once
+            config.fill_in(&mut dice::seeded_rng(seed));
+            config
+        }
+        None => {
+            // roll a random roster once and reuse it for every run
+            let mut rng = dice::seeded_rng(seed);
+            let players = load_players(&args, None, &mut rng);
+            serialization::SimulationConfig {
+                version: None,
+                seed: Some(seed),
+                rules: rules::Rules::default(),
+                arena: arena::Arena::default(),
+                items: equipment::ItemCatalog::default(),
+                abilities: abilities::AbilityCatalog::default(),
+                templates: Default::default(),
+                players: players.iter().map(serialization::PlayerConfig::from).collect(),
+            }
+        }
+    };
+
+    println!("battlers/{} seed {}", VERSION, seed);
+    let stats = batch::run_batch(&config, args.runs, args.threads, seed);
+    println!("{}", stats);
+}
+
+fn run_cli_replay(file: &str) {
+    tracing_subscriber::fmt::init();
+    info!("battlers/{}", VERSION);
+
+    let replay = match replay::Replay::load(file) {
+        Ok(replay) => replay,
+        Err(e) => {
+            error!("Failed to load replay from {}: {}", file, e);
+            return;
+        }
+    };
+    info!("replaying battle recorded with seed {}", replay.seed);
+
+    let mut game = game::Game::new();
+    game.subscribe(Box::new(events::LogObserver));
+    game.arena = replay.arena.clone();
+    for player in replay.players() {
+        info!("{:?}", player);
+        game.players.push_back(player);
+    }
+
+    let mut playback = replay::Playback::new(&replay);
+    while playback.step(&mut game) {}
+    report_winner(&game, game.turns);
+}
+
+fn report_winner(game: &game::Game, rounds_elapsed: i32) {
+    if let Some(winner) = game.winning_side() {
+        let (curr, base) = game.remaining_armor();
         info!(
-            "{} is the winner in {} turns with {} of {} hits left",
-            game.players[0].name,
-            turns_elapsed,
-            game.players[0].armor.curr,
-            game.players[0].armor.base
+            "{} is the winner in {} rounds with {} of {} hits left",
+            winner,
+            rounds_elapsed,
+            curr,
+            base
         );
     } else {
         error!("inconclusive results")
     }
 }
 
-fn load_players(args: Args) -> Vec<player::Player> {
-    let mut rng: ThreadRng = rand::rng();
-    
-    match args.config {
-        Some(config_path) => {
-            // Load players from YAML configuration
-            match serialization::load_simulation_config(&config_path) {
-                Ok(config) => serialization::players_from_config(config),
-                Err(e) => {
-                    error!("Failed to load configuration from {}: {}", config_path, e);
-                    Vec::new()
-                }
-            }
+fn start_recording(args: &Args, seed: u64, game: &mut game::Game) -> Option<Rc<RefCell<replay::Replay>>> {
+    args.record.as_ref()?;
+    let recorder = Rc::new(RefCell::new(replay::Replay::new(seed, &game.arena, game.players.iter())));
+    game.subscribe(Box::new(recorder.clone()));
+    Some(recorder)
+}
+
+fn finish_recording(args: &Args, recorder: Option<Rc<RefCell<replay::Replay>>>) {
+    if let (Some(path), Some(recorder)) = (&args.record, recorder)
+        && let Err(e) = recorder.borrow().save(path)
+    {
+        eprintln!("Failed to save replay to {}: {}", path, e);
+    }
+}
+
+/// Load the configuration named on the command line, if any, giving up on the whole run
+/// when it has problems.
+fn load_config(args: &Args) -> Option<serialization::SimulationConfig> {
+    match serialization::load_simulation_config(args.config.as_ref()?) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
         }
+    }
+}
+
+/// The `--seed` flag wins over the configuration, otherwise a fresh seed is drawn.
+fn pick_seed(args: &Args, config: Option<&serialization::SimulationConfig>) -> u64 {
+    args.seed
+        .or_else(|| config.and_then(|config| config.seed))
+        .unwrap_or_else(|| rand::rng().random())
+}
+
+fn load_players<R: Rng + ?Sized>(
+    args: &Args,
+    config: Option<serialization::SimulationConfig>,
+    rng: &mut R,
+) -> Vec<player::Player> {
+    match config {
+        Some(config) => serialization::players_from_config(config, rng),
         None => {
             // Generate random players
             if args.players > MAX_PLAYERS {
-                error!("too many players requested, {} is the max", MAX_PLAYERS);
-                return Vec::new();
+                eprintln!("too many players requested, {} is the max", MAX_PLAYERS);
+                process::exit(1);
             }
             
-            let mut players = Vec::new();
-            for _ in 0..args.players {
-                let mut player = player::Player::new(names::get_random_name(&mut rng));
-                player.randomize(&mut rng);
-                players.push(player);
-            }
-            players
+            player::random_roster(args.players as usize, &rules::Rules::default(), &arena::Arena::default(), rng)
         }
     }
 }
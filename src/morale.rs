@@ -0,0 +1,94 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::player::Player;
+use crate::rules::Rules;
+
+/// Where a player stands once its nerve has been tested.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MoraleState {
+    #[default]
+    Steady,
+    /// Running for the edge of the field, no longer counted as fighting.
+    Routed,
+    /// Laid down its arms and left the battle.
+    Surrendered,
+    /// Routed and got far enough away to leave the battle.
+    Escaped,
+}
+
+impl MoraleState {
+    /// Whether the player has left the battle for good.
+    pub fn is_withdrawn(&self) -> bool {
+        matches!(self, MoraleState::Surrendered | MoraleState::Escaped)
+    }
+}
+
+impl fmt::Display for MoraleState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MoraleState::Steady => "steady",
+            MoraleState::Routed => "routed",
+            MoraleState::Surrendered => "surrendered",
+            MoraleState::Escaped => "escaped",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The result of testing a player's nerve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoraleCheck {
+    pub roll: i32,
+    pub morale: i32,
+    pub outcome: MoraleState,
+}
+
+impl MoraleCheck {
+    pub fn passed(&self) -> bool {
+        self.outcome == MoraleState::Steady
+    }
+}
+
+/// Roll the rules' morale dice against the player's morale, rolling at or under it
+/// holds. Failing by `surrender_margin` or more means surrender rather than a rout.
+/// Fearless players, those with a morale of 0, and players that already broke are not tested.
+pub fn check<R: Rng + ?Sized>(player: &Player, rules: &Rules, rng: &mut R) -> Option<MoraleCheck> {
+    if player.is_fearless() || player.morale_state != MoraleState::Steady {
+        return None;
+    }
+    let roll = rules.morale.roll(rng).total;
+    let morale = player.morale.curr;
+    let outcome = if roll <= morale {
+        MoraleState::Steady
+    } else if roll - morale >= rules.surrender_margin {
+        MoraleState::Surrendered
+    } else {
+        MoraleState::Routed
+    };
+    Some(MoraleCheck { roll, morale, outcome })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks_depend_on_morale() {
+        let rules = Rules::default();
+        let mut rng = crate::dice::seeded_rng(1);
+        let mut player = Player::new("P");
+
+        assert_eq!(check(&player, &rules, &mut rng), None);
+        player.morale.set(18);
+        assert!(check(&player, &rules, &mut rng).unwrap().passed());
+        player.morale.set(2);
+        let failed = check(&player, &rules, &mut rng).unwrap();
+        assert_ne!(failed.outcome, MoraleState::Steady);
+        assert_eq!(failed.outcome == MoraleState::Surrendered, failed.roll - 2 >= rules.surrender_margin);
+        player.morale_state = MoraleState::Routed;
+        assert_eq!(check(&player, &rules, &mut rng), None);
+    }
+}
@@ -1,7 +1,11 @@
+use rand::Rng;
+use std::collections::HashSet;
 use rand::prelude::IndexedRandom;
-use rand::rngs::ThreadRng;
 use tracing::error;
 
+/// Random names are drawn this many times looking for one nobody has before they get numbered.
+const MAX_NAME_DRAWS: u32 = 100;
+
 const NAMES: &str = r"Abban Adomn Adhamh Adhamhnán Adamnan Eunan Adanodan Ailbhe Ailbe Alby
 Ailgel Ailill Ailín Aininn Ainmire Airechtach Airmedach Alabhaois Alaios
 Alastrann Alchad Alstrom Amalgaid Amergin Anluan Anlon Aodh Aodha Aoidh
@@ -242,7 +246,7 @@ Vran Vron Wannard Weonard";
 ///
 /// * An `Option<&str>` which is `Some(&str)` containing the randomly selected word,
 ///   or `None` if the input string is empty or contains only whitespace.
-fn random_word_from_text<'a>(text: &'a str, rng: &mut ThreadRng) -> Option<&'a str> {
+fn random_word_from_text<'a, R: Rng + ?Sized>(text: &'a str, rng: &mut R) -> Option<&'a str> {
     // Collect the words into a `Vec`. This Vec can be used as a slice.
     let words: Vec<&str> = text.split_whitespace().collect();
 
@@ -252,11 +256,26 @@ fn random_word_from_text<'a>(text: &'a str, rng: &mut ThreadRng) -> Option<&'a s
     words.choose(rng).copied()
 }
 
-pub fn get_random_name(rng: &mut ThreadRng) -> &str {
+pub fn get_random_name<R: Rng + ?Sized>(rng: &mut R) -> &'static str {
     if let Some(name) = random_word_from_text(NAMES, rng) {
         name
     } else {
         error!("unable to get a random string from names list!");
-        &""
+        ""
+    }
+}
+
+/// A random name that is not among the `taken` ones.
+pub fn unused_name<R: Rng + ?Sized>(taken: &HashSet<String>, rng: &mut R) -> String {
+    for _ in 0..MAX_NAME_DRAWS {
+        let name = get_random_name(rng);
+        if !taken.contains(name) {
+            return name.to_string();
+        }
     }
+    let name = get_random_name(rng);
+    (2..)
+        .map(|n| format!("{} {}", name, n))
+        .find(|numbered| !taken.contains(numbered))
+        .unwrap()
 }
@@ -0,0 +1,153 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::arena::{Arena, Mover};
+use crate::player::Location;
+
+/// The search gives up after visiting this many grid points.
+const MAX_EXPANSIONS: usize = 20_000;
+
+const NEIGHBOURS: [(i32, i32); 8] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+type Cell = (i32, i32);
+
+/// A grid point waiting to be visited, ordered so the heap pops the cheapest estimate first.
+#[derive(PartialEq)]
+struct Candidate {
+    estimate: f32,
+    cell: Cell,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| other.cell.cmp(&self.cell))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A* over the arena's grid points, a unit apart: the cheapest route from `from` to within
+/// `reach` of `to` with no wall blocking sight of it, around walls and the mover's crowd.
+/// Walkers follow the ground, paying double through difficult terrain and for every bit
+/// of height they climb; flyers level out with the target and cross any ground alike.
+/// Either pays double for moving on from next to an enemy. Returns the points to pass
+/// through, `None` when there is no way there.
+pub fn find_path(arena: &Arena, from: &Location, to: &Location, reach: f32, mover: &Mover) -> Option<Vec<Location>> {
+    let flying = mover.flying;
+    let point = |(x, y): Cell| {
+        let mut spot = Location::new(x as f32, y as f32, 0.0);
+        let ground = arena.ground_at(&spot);
+        spot.z = if flying { to.z.max(ground) } else { ground };
+        spot
+    };
+    let left_to_go = |loc: &Location| (loc.horizontal_distance(to) - reach).max(0.0);
+    let open_ground = |loc: &Location| {
+        !arena.is_blocked(loc) && mover.crowd.iter().all(|unit| !unit.overlaps(loc, mover.size))
+    };
+
+    let start = (from.x.round() as i32, from.y.round() as i32);
+    let mut queue = BinaryHeap::new();
+    let mut cost: HashMap<Cell, f32> = HashMap::new();
+    let mut came_from: HashMap<Cell, Cell> = HashMap::new();
+    cost.insert(start, 0.0);
+    queue.push(Candidate {
+        estimate: left_to_go(&point(start)),
+        cell: start,
+    });
+
+    let mut expanded = 0;
+    while let Some(Candidate { estimate, cell }) = queue.pop() {
+        let here = point(cell);
+        let spent = cost[&cell];
+        if estimate > spent + left_to_go(&here) {
+            // a cheaper way here was found after this one was queued
+            continue;
+        }
+        if here.distance(to) <= reach && !arena.blocks_sight(&here, to) {
+            let mut path = vec![here];
+            let mut cell = cell;
+            while let Some(&previous) = came_from.get(&cell) {
+                cell = previous;
+                path.push(point(cell));
+            }
+            path.pop();
+            path.reverse();
+            return Some(path);
+        }
+        expanded += 1;
+        if expanded > MAX_EXPANSIONS {
+            break;
+        }
+        for (dx, dy) in NEIGHBOURS {
+            let next = (cell.0 + dx, cell.1 + dy);
+            let there = point(next);
+            // diagonal steps must not cut the corner of a wall
+            let between = Location::new((here.x + there.x) / 2.0, (here.y + there.y) / 2.0, here.z);
+            if !open_ground(&there) || arena.is_blocked(&between) {
+                continue;
+            }
+            let step = if flying {
+                here.distance(&there)
+            } else {
+                here.horizontal_distance(&there) * arena.movement_cost(&there) + (there.z - here.z).max(0.0)
+            };
+            let total = spent + step * mover.zone_cost(&here);
+            if cost.get(&next).is_none_or(|&known| total < known) {
+                cost.insert(next, total);
+                came_from.insert(next, cell);
+                queue.push(Candidate {
+                    estimate: total + left_to_go(&there),
+                    cell: next,
+                });
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::{Footprint, Region};
+
+    const WALKER: Mover = Mover { size: 0.5, flying: false, crowd: &[] };
+
+    fn at(x: f32, y: f32) -> Location {
+        Location::new(x, y, 0.0)
+    }
+
+    #[test]
+    fn test_path_goes_around_walls_and_units() {
+        let mut arena = Arena::new(20.0, 9.0);
+        arena.walls.push(Region { x: 9.5, y: 0.0, width: 1.0, height: 7.0 });
+
+        let path = find_path(&arena, &at(5.0, 2.0), &at(15.0, 2.0), 1.0, &WALKER).unwrap();
+        assert!(path.iter().all(|p| !arena.is_blocked(p)));
+        assert!(path.iter().any(|p| p.y >= 7.0));
+        assert!(path.last().unwrap().distance(&at(15.0, 2.0)) <= 1.0);
+
+        // a unit standing in the gap closes it
+        let crowd = [Footprint { loc: at(10.0, 8.5), size: 0.5, hostile: false }];
+        let mover = Mover { crowd: &crowd, ..WALKER };
+        assert!(find_path(&arena, &at(5.0, 2.0), &at(15.0, 2.0), 1.0, &mover).is_none());
+    }
+
+    #[test]
+    fn test_path_avoids_difficult_terrain_when_cheaper() {
+        let mut arena = Arena::new(20.0, 10.0);
+        arena.difficult.push(Region { x: 8.0, y: 0.0, width: 4.0, height: 4.0 });
+
+        let path = find_path(&arena, &at(2.0, 2.0), &at(18.0, 2.0), 0.0, &WALKER).unwrap();
+        assert!(path.iter().all(|p| arena.movement_cost(p) == 1.0));
+        assert_eq!(path.last(), Some(&at(18.0, 2.0)));
+    }
+}
@@ -1,10 +1,23 @@
 use core::f32;
-use rand::{Rng, rngs::ThreadRng};
+use std::collections::HashSet;
+use std::fmt;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use tracing::{debug, warn};
 
-use crate::dice;
+use crate::behavior::BehaviorProfile;
+use crate::abilities::AbilitySlot;
+use crate::arena::{Arena, Footprint, Mover};
+use crate::dice::DiceExpr;
+use crate::effects::{self, Condition, StatusEffect};
+use crate::equipment::Equipment;
+use crate::morale::MoraleState;
+use crate::names;
+use crate::pathfinding;
+use crate::rules::{CritDamage, Rules};
+use crate::targeting::Targeting;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Attribute {
     Attack,
     Defense,
@@ -12,6 +25,8 @@ pub enum Attribute {
     Power,
     Speed,
     Range,
+    Regen,
+    Morale,
 }
 
 #[derive(Debug)]
@@ -24,7 +39,7 @@ pub struct PlayerAttribute {
 impl PlayerAttribute {
     pub fn new(name: Attribute) -> Self {
         PlayerAttribute {
-            name: name,
+            name,
             base: 0,
             curr: 0,
         }
@@ -35,14 +50,32 @@ impl PlayerAttribute {
     }
     pub fn bonus(&self) -> i32 {
         let bv: f32 = (self.curr as f32 - 10.5) / 2.0;
-        return bv as i32;
+        bv as i32
+    }
+    pub fn randomize<R: Rng + ?Sized>(&mut self, rules: &Rules, rng: &mut R) {
+        self.set(rules.roll_attribute(self.name, rng));
     }
-    pub fn randomize(&mut self, rng: &mut ThreadRng) {
-        self.set(dice::roll3d6(rng));
+}
+
+impl fmt::Display for PlayerAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {}/{}", self.name, self.curr, self.base)
     }
 }
 
-#[derive(Debug, Clone)]
+/// Advancing players stop this far inside their range, so rounding never leaves them
+/// just short of it.
+const CLOSING_MARGIN: f32 = 0.5;
+/// How much higher than its target an attacker has to be to have the high ground.
+const HIGH_GROUND: f32 = 1.0;
+/// Random placement gives up after this many spots turn out to be walls.
+const MAX_PLACEMENT_TRIES: u32 = 1000;
+/// How far a player's footprint reaches from where it stands unless told otherwise.
+pub const DEFAULT_SIZE: f32 = 0.5;
+/// Players whose footprints are no further apart than this are touching.
+const TOUCHING: f32 = 0.1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Location {
     pub x: f32,
     pub y: f32,
@@ -51,7 +84,7 @@ pub struct Location {
 
 impl Location {
     pub fn new(x: f32, y: f32, z: f32) -> Self {
-        Location { x: x, y: y, z: z }
+        Location { x, y, z }
     }
     pub fn distance(&self, target: &Location) -> f32 {
         let dx = self.x - target.x;
@@ -63,72 +96,276 @@ impl Location {
         let i = pdx + pdy + pdz;
         i.sqrt()
     }
-    pub fn randomize(&mut self, rng: &mut ThreadRng) {
-        let roll_x = rng.random_range(1..=60);
-        let roll_y = rng.random_range(1..=60);
-        self.x = roll_x as f32;
-        self.y = roll_y as f32;
-        self.z = 0.0;
+    /// Distance across the ground, whatever the difference in height.
+    pub fn horizontal_distance(&self, target: &Location) -> f32 {
+        let dx = self.x - target.x;
+        let dy = self.y - target.y;
+        (dx.powf(2.0) + dy.powf(2.0)).sqrt()
+    }
+    /// Pick a random spot in the arena that is not inside a wall.
+    pub fn randomize<R: Rng + ?Sized>(&mut self, arena: &Arena, rng: &mut R) {
+        for _ in 0..MAX_PLACEMENT_TRIES {
+            let roll_x = rng.random_range(1..=(arena.width as i32).max(1));
+            let roll_y = rng.random_range(1..=(arena.height as i32).max(1));
+            self.x = roll_x as f32;
+            self.y = roll_y as f32;
+            self.z = arena.ground_at(self);
+            if !arena.is_blocked(self) {
+                return;
+            }
+        }
+        warn!("no open ground found, {}:{} is inside a wall", self.x, self.y);
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({:.1}, {:.1}", self.x, self.y)?;
+        if self.z != 0.0 {
+            write!(f, ", {:.1}", self.z)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AttackRoll {
+    pub roll: i32,
+    /// The face the die showed, before any modifiers in the attack dice.
+    pub natural: i32,
+    pub bonus: i32,
+    /// The target's defense, cover included.
+    pub target_defense: i32,
+    /// How much of the defense came from cover between attacker and target.
+    pub cover: i32,
+    pub critical: bool,
+    pub fumble: bool,
+}
+
+impl AttackRoll {
+    pub fn is_hit(&self) -> bool {
+        !self.fumble && (self.critical || self.roll + self.bonus >= self.target_defense)
     }
 }
 
 #[derive(Debug)]
 pub struct Player {
     pub name: String,
+    pub team: Option<String>,
+    pub targeting: Targeting,
+    pub behavior: BehaviorProfile,
     pub attack: PlayerAttribute,
     pub defense: PlayerAttribute,
     pub armor: PlayerAttribute,
     pub power: PlayerAttribute,
     pub speed: PlayerAttribute,
     pub range: PlayerAttribute,
+    /// Armor restored at the start of each of the player's turns.
+    pub regen: PlayerAttribute,
+    /// What morale checks are rolled against; players with a morale of 0 are fearless.
+    pub morale: PlayerAttribute,
+    pub morale_state: MoraleState,
     pub loc: Location,
+    /// How far the player's footprint reaches from where it stands.
+    pub size: f32,
+    /// Flies over difficult terrain and raised ground alike and never falls.
+    pub flying: bool,
+    /// The rest of the route the player is following, where it heads next.
+    pub route: Vec<Location>,
+    pub equipment: Equipment,
+    pub effects: Vec<StatusEffect>,
+    pub abilities: Vec<AbilitySlot>,
+    /// Scheduler tick at which this player may act again.
+    pub ready_at: i32,
+    /// Initiative rolled at the start of the current round.
+    pub initiative: i32,
 }
 
 impl Player {
     pub fn new(name: &str) -> Self {
         Player {
             name: String::from(name),
+            team: None,
+            targeting: Targeting::default(),
+            behavior: BehaviorProfile::default(),
             attack: PlayerAttribute::new(Attribute::Attack),
             defense: PlayerAttribute::new(Attribute::Defense),
             armor: PlayerAttribute::new(Attribute::Armor),
             power: PlayerAttribute::new(Attribute::Power),
             speed: PlayerAttribute::new(Attribute::Speed),
             range: PlayerAttribute::new(Attribute::Range),
+            regen: PlayerAttribute::new(Attribute::Regen),
+            morale: PlayerAttribute::new(Attribute::Morale),
+            morale_state: MoraleState::default(),
             loc: Location::new(0.0, 0.0, 0.0),
+            size: DEFAULT_SIZE,
+            flying: false,
+            route: Vec::new(),
+            equipment: Equipment::default(),
+            effects: Vec::new(),
+            abilities: Vec::new(),
+            ready_at: 0,
+            initiative: 0,
+        }
+    }
+    pub fn randomize<R: Rng + ?Sized>(&mut self, rules: &Rules, arena: &Arena, rng: &mut R) {
+        self.attack.randomize(rules, rng);
+        self.defense.randomize(rules, rng);
+        self.armor.randomize(rules, rng);
+        self.power.randomize(rules, rng);
+        self.speed.randomize(rules, rng);
+        self.range.randomize(rules, rng);
+        self.morale.randomize(rules, rng);
+        self.loc.randomize(arena, rng);
+    }
+    /// The room the player takes up, as seen by `other` moving past it.
+    pub fn footprint(&self, other: &Player) -> Footprint {
+        Footprint {
+            loc: self.loc.clone(),
+            size: self.size,
+            hostile: !self.is_ally(other),
+        }
+    }
+    fn mover<'a>(&self, crowd: &'a [Footprint]) -> Mover<'a> {
+        Mover {
+            size: self.size,
+            flying: self.flying,
+            crowd,
+        }
+    }
+    /// Close in on `target` along the cheapest path around walls and the `bystanders`,
+    /// stopping once it is within the player's range with nothing blocking sight or up
+    /// against the target. Returns the height of the highest drop on the way.
+    pub fn move_towards(&mut self, target: &Player, arena: &Arena, bystanders: &[Footprint]) -> f32 {
+        let goal = &target.loc;
+        let distance = self.loc.distance(goal);
+        let speed = self.speed.curr as f32;
+        let reach = (self.effective_range() as f32 - CLOSING_MARGIN).max(0.0);
+        if distance <= reach && !arena.blocks_sight(&self.loc, goal) {
+            return 0.0;
+        }
+        let route = match pathfinding::find_path(arena, &self.loc, goal, reach, &self.mover(bystanders)) {
+            Some(route) if !route.is_empty() => route,
+            _ => {
+                // nowhere to go around, or only height left to make up, so head straight for it
+                let step = speed.min(distance - reach);
+                if step > 0.0 {
+                    let t = step / distance;
+                    vec![Location::new(
+                        self.loc.x + (goal.x - self.loc.x) * t,
+                        self.loc.y + (goal.y - self.loc.y) * t,
+                        self.loc.z + (goal.z - self.loc.z) * t,
+                    )]
+                } else {
+                    Vec::new()
+                }
+            }
+        };
+        let mut crowd = bystanders.to_vec();
+        crowd.push(target.footprint(self));
+        let travel = arena.follow(&self.loc, &route, speed, &self.mover(&crowd));
+        debug!("{} moved to {}", self.loc, travel.end);
+        self.loc = travel.end;
+        self.route = route[travel.reached..].to_vec();
+        travel.fall
+    }
+    /// Move `distance` straight at `target`, however close that gets without running into
+    /// anybody in the `crowd`.
+    pub fn rush_towards(&mut self, target: &Location, distance: f32, arena: &Arena, crowd: &[Footprint]) -> f32 {
+        self.move_away(target, -distance, arena, crowd)
+    }
+    /// Back off `distance` from `threat` across the ground, around the `crowd`, returning
+    /// the height of the highest drop on the way.
+    pub fn move_away(&mut self, threat: &Location, distance: f32, arena: &Arena, crowd: &[Footprint]) -> f32 {
+        let current = self.loc.horizontal_distance(threat);
+        let (dx_normalized, dy_normalized) = if current > 0.0 {
+            ((self.loc.x - threat.x) / current, (self.loc.y - threat.y) / current)
+        } else {
+            (1.0, 0.0)
+        };
+        let goal = Location::new(self.loc.x + dx_normalized * distance, self.loc.y + dy_normalized * distance, self.loc.z);
+        let travel = arena.advance(&self.loc, &goal, distance.abs(), &self.mover(crowd));
+        debug!("{} backed off to {}", self.loc, travel.end);
+        self.loc = travel.end;
+        self.route.clear();
+        travel.fall
+    }
+    /// How far the player can attack, its weapon's reach if it carries one.
+    pub fn effective_range(&self) -> i32 {
+        match &self.equipment.weapon {
+            Some((_, weapon)) => weapon.reach(),
+            None => self.range.curr,
+        }
+    }
+    pub fn effective_defense(&self) -> i32 {
+        let armor = self.equipment.armor.as_ref().map_or(0, |(_, armor)| armor.defense);
+        let shield = self
+            .effects
+            .iter()
+            .filter(|e| e.condition == Condition::Shielded)
+            .map(|e| e.potency)
+            .max()
+            .unwrap_or(0);
+        self.defense.curr + armor + shield
+    }
+    /// Whether `target` is close enough to attack with no wall in the way. Players touching
+    /// can always trade blows, whatever their range.
+    pub fn in_range(&self, target: &Player, arena: &Arena) -> bool {
+        let reach = (self.effective_range() as f32).max(self.size + target.size + TOUCHING);
+        self.loc.distance(&target.loc) <= reach && !arena.blocks_sight(&self.loc, &target.loc)
+    }
+    /// Roll to hit `target`, which defends `cover` better for what is in the way. Attacking
+    /// from higher up earns the high ground bonus.
+    pub fn attack<R: Rng + ?Sized>(&self, target: &Player, rules: &Rules, cover: i32, rng: &mut R) -> AttackRoll {
+        let roll = rules.attack.roll(rng);
+        debug!("{} rolled {} to hit {}", self.name, roll, target.name);
+        let weapon = self.equipment.weapon.as_ref().map(|(_, weapon)| weapon);
+        let weapon_bonus = weapon.map_or(0, |w| w.to_hit_at(self.loc.distance(&target.loc)).unwrap_or(0));
+        let crit_range = weapon.and_then(|w| w.crit_range).unwrap_or(rules.crit_range);
+        let natural = roll.natural();
+        let fumble = natural <= rules.fumble_range;
+        let high_ground = if self.loc.z >= target.loc.z + HIGH_GROUND { rules.high_ground } else { 0 };
+        AttackRoll {
+            roll: roll.total,
+            natural,
+            bonus: self.attack.bonus() + weapon_bonus + high_ground,
+            target_defense: target.effective_defense() + cover,
+            cover,
+            critical: !fumble && natural >= crit_range,
+            fumble,
+        }
+    }
+    /// The dice the player's basic attack does damage with.
+    pub fn damage_dice<'a>(&'a self, rules: &'a Rules) -> &'a DiceExpr {
+        match &self.equipment.weapon {
+            Some((_, weapon)) => &weapon.damage,
+            None => &rules.damage,
         }
     }
-    pub fn randomize(&mut self, rng: &mut ThreadRng) {
-        self.attack.randomize(rng);
-        self.defense.randomize(rng);
-        self.armor.randomize(rng);
-        self.power.randomize(rng);
-        self.speed.set(dice::roll1d8(rng) + 10);
-        self.range.randomize(rng);
-        self.loc.randomize(rng);
-    }
-    pub fn move_towards(&mut self, target: &Location) {
-        let distance = self.loc.distance(target);
-        if distance <= self.speed.curr as f32 {
-            return;
-        }
-        let dx_normalized = (target.x - self.loc.x) / distance;
-        let dy_normalized = (target.y - self.loc.y) / distance;
-        let new_x = self.loc.x + dx_normalized * self.speed.curr as f32;
-        let new_y = self.loc.y + dy_normalized * self.speed.curr as f32;
-        debug!("{}:{} moved to {}:{}", self.loc.x, self.loc.y, new_x, new_y);
-        self.loc.x = new_x;
-        self.loc.y = new_y;
-    }
-    pub fn in_range(&self, target: &Location) -> bool {
-        let range = self.loc.distance(target);
-        range <= self.range.curr as f32
-    }
-    pub fn attack(&self, target: &Player, rng: &mut ThreadRng) -> bool {
-        let roll = dice::roll1d20(rng);
-        self.attack.bonus() + roll >= target.defense.curr
-    }
-    pub fn damage(&self, target: &mut Player, rng: &mut ThreadRng) -> i32 {
-        let damage_inflicted = dice::roll1d8(rng) + self.power.bonus();
+    /// Roll `dice` plus power bonus against `target`, `critical` tells how a critical
+    /// hit adds to the dice.
+    pub fn damage<R: Rng + ?Sized>(
+        &self,
+        target: &mut Player,
+        dice: &DiceExpr,
+        critical: Option<CritDamage>,
+        rng: &mut R,
+    ) -> i32 {
+        let rolled = match critical {
+            Some(CritDamage::Maximize) => dice.maximum(),
+            Some(CritDamage::ExtraDice) => {
+                let roll = dice.roll(rng);
+                let extra = dice.roll(rng);
+                debug!("{} rolled {} and {} for critical damage", self.name, roll, extra);
+                roll.total + extra.total - extra.modifier
+            }
+            None => {
+                let roll = dice.roll(rng);
+                debug!("{} rolled {} for damage", self.name, roll);
+                roll.total
+            }
+        };
+        let damage_inflicted = rolled + self.power.bonus();
         if damage_inflicted < 1 {
             warn!("no damage inflicted!");
             return 0
@@ -136,7 +373,136 @@ impl Player {
         target.armor.curr -= damage_inflicted;
         damage_inflicted
     }
+    pub fn has_condition(&self, condition: Condition) -> bool {
+        self.effects.iter().any(|e| e.condition == condition)
+    }
+    pub fn add_effect(&mut self, effect: StatusEffect) {
+        effects::stack(&mut self.effects, effect);
+        self.apply_effects();
+    }
+    /// Drop one instance of `condition`, as when it runs out.
+    pub fn remove_effect(&mut self, condition: Condition) {
+        if let Some(idx) = self.effects.iter().position(|e| e.condition == condition) {
+            self.effects.remove(idx);
+        }
+        self.apply_effects();
+    }
+    /// The first consumable with charges left, once the player is down to half armor.
+    pub fn consumable_to_use(&self) -> Option<usize> {
+        if self.armor.curr * 2 > self.armor.base {
+            return None;
+        }
+        self.equipment.consumables.iter().position(|slot| slot.charges > 0)
+    }
+    /// Restore up to `amount` armor without going over the base value, returning how
+    /// much was restored.
+    pub fn heal(&mut self, amount: i32) -> i32 {
+        let restored = amount.clamp(0, (self.armor.base - self.armor.curr).max(0));
+        self.armor.curr += restored;
+        restored
+    }
+    /// One turn closer to every ability being ready again.
+    pub fn tick_cooldowns(&mut self) {
+        for slot in self.abilities.iter_mut() {
+            slot.cooldown = (slot.cooldown - 1).max(0);
+        }
+    }
+    /// Let every effect act for one of the player's turns, damaging effects hurt and
+    /// all of them get a turn shorter. Returns the effects that dealt damage and the
+    /// ones that ran out.
+    pub fn tick_effects(&mut self) -> (Vec<StatusEffect>, Vec<StatusEffect>) {
+        let hurting: Vec<StatusEffect> = self
+            .effects
+            .iter()
+            .filter(|e| e.condition.deals_damage())
+            .cloned()
+            .collect();
+        for effect in &hurting {
+            self.armor.curr -= effect.potency;
+        }
+        for effect in self.effects.iter_mut() {
+            effect.turns -= 1;
+        }
+        let (expired, active) = std::mem::take(&mut self.effects)
+            .into_iter()
+            .partition(|e| e.turns <= 0);
+        self.effects = active;
+        self.apply_effects();
+        (hurting, expired)
+    }
+    /// Recompute the attributes effects modify from their base values.
+    fn apply_effects(&mut self) {
+        let slow = self
+            .effects
+            .iter()
+            .filter(|e| e.condition == Condition::Slowed)
+            .map(|e| e.potency)
+            .max();
+        self.speed.curr = match slow {
+            Some(slow) => (self.speed.base - slow).max(1),
+            None => self.speed.base,
+        };
+    }
+    /// The team this player fights for; players without a team fight for themselves.
+    pub fn side(&self) -> &str {
+        self.team.as_deref().unwrap_or(&self.name)
+    }
+    pub fn is_ally(&self, other: &Player) -> bool {
+        self.side() == other.side()
+    }
+    pub fn is_fearless(&self) -> bool {
+        self.morale.base <= 0
+    }
+    /// Whether the player still counts towards its team holding the field.
+    pub fn is_steady(&self) -> bool {
+        self.morale_state == MoraleState::Steady
+    }
     pub fn is_dead(&self) -> bool {
         self.armor.curr < 1
     }
 }
+
+/// `count` randomly rolled players, no two of them sharing a name.
+pub fn random_roster<R: Rng + ?Sized>(count: usize, rules: &Rules, arena: &Arena, rng: &mut R) -> Vec<Player> {
+    let mut taken = HashSet::new();
+    let mut players = Vec::new();
+    for _ in 0..count {
+        let name = names::unused_name(&taken, rng);
+        let mut player = Player::new(&name);
+        player.randomize(rules, arena, rng);
+        taken.insert(name);
+        players.push(player);
+    }
+    players
+}
+
+#[cfg(test)]
+impl Player {
+    /// A plain fighter standing at `x` for tests to tweak: 10 in every attribute but a
+    /// speed of 5 and a range of 2.
+    pub fn fighter(name: &str, x: f32) -> Player {
+        let mut player = Player::new(name);
+        player.attack.set(10);
+        player.defense.set(10);
+        player.armor.set(10);
+        player.power.set(10);
+        player.speed.set(5);
+        player.range.set(2);
+        player.loc.x = x;
+        player
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_roster_names_are_unique() {
+        for seed in [1, 3, 5] {
+            let roster = random_roster(64, &Rules::default(), &Arena::default(), &mut crate::dice::seeded_rng(seed));
+            let names: HashSet<&str> = roster.iter().map(|p| p.name.as_str()).collect();
+            assert_eq!(names.len(), 64);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use tracing::{error, info};
+
+use crate::events::{GameEvent, Observer};
+use crate::game::Game;
+use crate::player::Player;
+use crate::abilities::AbilityCatalog;
+use crate::arena::Arena;
+use crate::equipment::ItemCatalog;
+use crate::serialization::{self, PlayerConfig};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplayTurn {
+    pub turn: i32,
+    pub events: Vec<GameEvent>,
+}
+
+/// A complete battle: the roster it started with, the seed it was played with and
+/// every event the engine emitted, grouped by turn.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+    pub seed: u64,
+    pub players: Vec<PlayerConfig>,
+    /// The items the players carried.
+    #[serde(default)]
+    pub items: ItemCatalog,
+    #[serde(default)]
+    pub abilities: AbilityCatalog,
+    /// The field the battle was fought on.
+    #[serde(default)]
+    pub arena: Arena,
+    pub turns: Vec<ReplayTurn>,
+}
+
+impl Replay {
+    pub fn new<'a>(seed: u64, arena: &Arena, players: impl IntoIterator<Item = &'a Player>) -> Self {
+        let mut items = ItemCatalog::default();
+        let mut abilities = AbilityCatalog::default();
+        let players = players
+            .into_iter()
+            .map(|player| {
+                items.insert(&player.equipment);
+                for slot in &player.abilities {
+                    abilities.insert(slot.name.clone(), slot.ability.clone());
+                }
+                PlayerConfig::from(player)
+            })
+            .collect();
+        Replay {
+            seed,
+            players,
+            items,
+            abilities,
+            arena: arena.clone(),
+            turns: Vec::new(),
+        }
+    }
+    pub fn players(&self) -> Vec<Player> {
+        self.players
+            .iter()
+            .cloned()
+            .map(|config| serialization::player_from_config(config, &self.items, &self.abilities))
+            .collect()
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let content = serde_yaml::to_string(self)?;
+        fs::write(path, content).map_err(|e| {
+            error!("Failed to write replay {}: {}", path.display(), e);
+            e
+        })?;
+        info!("Saved replay of {} turns to {}", self.turns.len(), path.display());
+        Ok(())
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|e| {
+            error!("Failed to read replay {}: {}", path.display(), e);
+            e
+        })?;
+        let replay: Replay = serde_yaml::from_str(&content).map_err(|e| {
+            error!("Failed to parse replay {}: {}", path.display(), e);
+            e
+        })?;
+        Ok(replay)
+    }
+}
+
+impl Observer for Replay {
+    fn on_event(&mut self, turn: i32, event: &GameEvent) {
+        match self.turns.last_mut() {
+            Some(last) if last.turn == turn => last.events.push(event.clone()),
+            _ => self.turns.push(ReplayTurn {
+                turn,
+                events: vec![event.clone()],
+            }),
+        }
+    }
+}
+
+/// Feeds a recorded battle back into a `Game` one turn at a time.
+pub struct Playback {
+    turns: VecDeque<ReplayTurn>,
+}
+
+impl Playback {
+    pub fn new(replay: &Replay) -> Self {
+        Playback {
+            turns: replay.turns.iter().cloned().collect(),
+        }
+    }
+    pub fn is_finished(&self) -> bool {
+        self.turns.is_empty()
+    }
+    /// Apply the next recorded turn, returns `false` once the recording is exhausted.
+    pub fn step(&mut self, game: &mut Game) -> bool {
+        match self.turns.pop_front() {
+            Some(turn) => {
+                for event in turn.events {
+                    game.apply(turn.turn, event);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_playback_reproduces_recorded_battle() {
+        let mut rng = dice::seeded_rng(11);
+        let mut game = Game::new();
+        for name in ["A", "B", "C"] {
+            let mut player = Player::new(name);
+            player.randomize(&game.rules, &game.arena, &mut rng);
+            game.players.push_back(player);
+        }
+        let recorder = Rc::new(RefCell::new(Replay::new(11, &game.arena, game.players.iter())));
+        game.subscribe(Box::new(recorder.clone()));
+        game.run_simulation(&mut rng);
+
+        let text = serde_yaml::to_string(&*recorder.borrow()).unwrap();
+        let replay: Replay = serde_yaml::from_str(&text).unwrap();
+        let mut replayed = Game::new();
+        replayed.players.extend(replay.players());
+        let mut playback = Playback::new(&replay);
+        while playback.step(&mut replayed) {}
+
+        assert_eq!(replayed.turns, game.turns);
+        assert_eq!(replayed.players.len(), game.players.len());
+        for original in game.players.iter() {
+            let replayed = replayed.players.iter().find(|p| p.name == original.name).unwrap();
+            assert_eq!(replayed.armor.curr, original.armor.curr);
+            assert_eq!(replayed.loc, original.loc);
+        }
+    }
+
+    #[test]
+    fn test_playback_keeps_cooldowns_and_effects() {
+        let catalog: AbilityCatalog = serde_yaml::from_str(
+            "
+volley:
+  kind: volley
+  shots: 1
+  cooldown: 2
+  effects:
+    - condition: bleeding
+      turns: 3
+stone_skin:
+  kind: shield
+  amount: 1d4
+  turns: 2
+  cooldown: 3
+",
+        )
+        .unwrap();
+        let names = ["stone_skin", "volley"].map(String::from);
+        let mut rng = dice::seeded_rng(5);
+        let mut game = Game::new();
+        for (name, x) in [("A", 0.0), ("B", 1.0)] {
+            let mut player = Player::fighter(name, x);
+            player.armor.set(200);
+            player.abilities = crate::abilities::learn(&catalog, &names).unwrap();
+            game.players.push_back(player);
+        }
+        let recorder = Rc::new(RefCell::new(Replay::new(5, &game.arena, game.players.iter())));
+        game.subscribe(Box::new(recorder.clone()));
+        for _ in 0..9 {
+            game.step(&mut rng);
+        }
+
+        let replay = recorder.borrow().clone();
+        let mut replayed = Game::new();
+        replayed.players.extend(replay.players());
+        let mut playback = Playback::new(&replay);
+        while playback.step(&mut replayed) {}
+
+        let state = |player: &Player| {
+            let cooldowns: Vec<i32> = player.abilities.iter().map(|slot| slot.cooldown).collect();
+            let effects: Vec<_> = player.effects.iter().map(|e| (e.condition, e.turns, e.potency)).collect();
+            (player.armor.curr, cooldowns, effects)
+        };
+        for original in game.players.iter() {
+            let replayed = replayed.players.iter().find(|p| p.name == original.name).unwrap();
+            assert_eq!(state(replayed), state(original));
+        }
+        assert!(game.players.iter().any(|p| !p.effects.is_empty()));
+        assert!(game.players.iter().any(|p| p.abilities.iter().any(|slot| slot.cooldown > 0)));
+    }
+}
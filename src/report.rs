@@ -0,0 +1,198 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use std::io::Write;
+
+use crate::game::Game;
+use crate::morale::MoraleState;
+use crate::player::{Location, Player, PlayerAttribute};
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Json,
+    Csv,
+    Yaml,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AttributeResult {
+    pub base: i32,
+    pub curr: i32,
+}
+
+impl From<&PlayerAttribute> for AttributeResult {
+    fn from(attribute: &PlayerAttribute) -> Self {
+        AttributeResult {
+            base: attribute.base,
+            curr: attribute.curr,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PlayerResult {
+    pub name: String,
+    pub team: Option<String>,
+    pub defeated: bool,
+    pub morale_state: MoraleState,
+    pub attack: AttributeResult,
+    pub defense: AttributeResult,
+    pub armor: AttributeResult,
+    pub power: AttributeResult,
+    pub speed: AttributeResult,
+    pub range: AttributeResult,
+    pub regen: AttributeResult,
+    pub morale: AttributeResult,
+    pub loc: Location,
+}
+
+impl PlayerResult {
+    fn new(player: &Player, defeated: bool) -> Self {
+        PlayerResult {
+            name: player.name.clone(),
+            team: player.team.clone(),
+            defeated,
+            morale_state: player.morale_state,
+            attack: AttributeResult::from(&player.attack),
+            defense: AttributeResult::from(&player.defense),
+            armor: AttributeResult::from(&player.armor),
+            power: AttributeResult::from(&player.power),
+            speed: AttributeResult::from(&player.speed),
+            range: AttributeResult::from(&player.range),
+            regen: AttributeResult::from(&player.regen),
+            morale: AttributeResult::from(&player.morale),
+            loc: player.loc.clone(),
+        }
+    }
+}
+
+/// The outcome of a single battle, in a shape meant for scripts rather than people.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BattleResult {
+    pub seed: u64,
+    pub winner: Option<String>,
+    pub turns_elapsed: i32,
+    pub inconclusive: bool,
+    pub players: Vec<PlayerResult>,
+}
+
+impl BattleResult {
+    pub fn new(game: &Game, seed: u64) -> Self {
+        let survivors = game.players.iter().map(|p| PlayerResult::new(p, false));
+        let fallen = game.fallen.iter().map(|p| PlayerResult::new(p, true));
+        let withdrawn = game.withdrawn.iter().map(|p| PlayerResult::new(p, false));
+        BattleResult {
+            seed,
+            winner: game.winning_side().map(String::from),
+            turns_elapsed: game.turns,
+            inconclusive: game.winning_side().is_none(),
+            players: survivors.chain(fallen).chain(withdrawn).collect(),
+        }
+    }
+
+    pub fn write(&self, format: OutputFormat, out: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+        match format {
+            OutputFormat::Json => {
+                serde_json::to_writer_pretty(&mut *out, self)?;
+                writeln!(out)?;
+            }
+            OutputFormat::Yaml => serde_yaml::to_writer(&mut *out, self)?,
+            OutputFormat::Csv => self.write_csv(out)?,
+        }
+        Ok(())
+    }
+
+    /// One row per player, with the battle level columns repeated on every row.
+    fn write_csv(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        let attributes = ["attack", "defense", "armor", "power", "speed", "range", "regen", "morale"];
+        let mut header = vec!["seed", "winner", "turns_elapsed", "inconclusive", "name", "team", "defeated", "morale_state"]
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        for attribute in attributes {
+            header.push(format!("{}_base", attribute));
+            header.push(format!("{}_curr", attribute));
+        }
+        header.extend(["x", "y", "z"].map(String::from));
+        writeln!(out, "{}", header.join(","))?;
+
+        for player in &self.players {
+            let mut row = vec![
+                self.seed.to_string(),
+                csv_field(self.winner.as_deref().unwrap_or("")),
+                self.turns_elapsed.to_string(),
+                self.inconclusive.to_string(),
+                csv_field(&player.name),
+                csv_field(player.team.as_deref().unwrap_or("")),
+                player.defeated.to_string(),
+                player.morale_state.to_string(),
+            ];
+            for attribute in [
+                &player.attack,
+                &player.defense,
+                &player.armor,
+                &player.power,
+                &player.speed,
+                &player.range,
+                &player.regen,
+                &player.morale,
+            ] {
+                row.push(attribute.base.to_string());
+                row.push(attribute.curr.to_string());
+            }
+            row.extend([player.loc.x, player.loc.y, player.loc.z].map(|v| v.to_string()));
+            writeln!(out, "{}", row.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finished_game() -> Game {
+        let mut game = Game::new();
+        let mut winner = Player::new("Winner, The");
+        winner.armor.set(10);
+        winner.armor.curr = 4;
+        let mut loser = Player::new("Loser");
+        loser.armor.set(8);
+        loser.armor.curr = -2;
+        game.players.push_back(winner);
+        game.fallen.push(loser);
+        game.turns = 12;
+        game
+    }
+
+    #[test]
+    fn test_result_from_finished_game() {
+        let result = BattleResult::new(&finished_game(), 3);
+
+        assert_eq!(result.winner.as_deref(), Some("Winner, The"));
+        assert!(!result.inconclusive);
+        assert_eq!(result.players.len(), 2);
+        assert!(result.players[1].defeated);
+        assert_eq!(result.players[0].armor, AttributeResult { base: 10, curr: 4 });
+    }
+
+    #[test]
+    fn test_csv_output_quotes_names() {
+        let result = BattleResult::new(&finished_game(), 3);
+        let mut out = Vec::new();
+        result.write(OutputFormat::Csv, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("seed,winner,turns_elapsed,inconclusive,name,team,defeated,morale_state,attack_base"));
+        assert!(lines[1].starts_with("3,\"Winner, The\",12,false,\"Winner, The\",,false,steady,"));
+    }
+}
@@ -0,0 +1,100 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::dice::DiceExpr;
+use crate::player::Attribute;
+
+fn dice(text: &str) -> DiceExpr {
+    DiceExpr::parse(text).expect("built-in dice expression")
+}
+
+/// The dice a battle is played with, set from the `rules:` section of the simulation YAML.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Rules {
+    /// Rolled for each attribute of a randomly generated player.
+    pub attribute: DiceExpr,
+    /// Rolled for the speed of a randomly generated player.
+    pub speed: DiceExpr,
+    /// Rolled to hit, the attacker's attack bonus is added on top.
+    pub attack: DiceExpr,
+    /// Rolled on a hit, the attacker's power bonus is added on top.
+    pub damage: DiceExpr,
+    /// Rolled at the start of every round, the player's speed bonus is added on top.
+    pub initiative: DiceExpr,
+    /// Natural attack rolls at or above this are critical hits.
+    pub crit_range: i32,
+    pub crit_damage: CritDamage,
+    /// Natural attack rolls at or below this miss no matter the bonus.
+    pub fumble_range: i32,
+    /// Rolled against a player's morale when its nerve is tested, at or under holds.
+    pub morale: DiceExpr,
+    /// Failing a morale check by this much or more means surrender instead of a rout.
+    pub surrender_margin: i32,
+    /// A routed player leaves the battle once no enemy is this close.
+    pub escape_distance: f32,
+    /// To-hit bonus for attacking a target standing lower down.
+    pub high_ground: i32,
+    /// Walkers can drop this far without getting hurt.
+    pub safe_fall: f32,
+    /// Rolled for every `safe_fall` of height a falling walker drops.
+    pub fall_damage: DiceExpr,
+}
+
+/// How a critical hit deals extra damage.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CritDamage {
+    /// Roll the damage dice a second time and add them, modifiers count once.
+    #[default]
+    ExtraDice,
+    /// Every damage die counts as its highest face.
+    Maximize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            attribute: dice("3d6"),
+            speed: dice("1d8+10"),
+            attack: dice("1d20"),
+            damage: dice("1d8"),
+            initiative: dice("1d20"),
+            crit_range: 20,
+            crit_damage: CritDamage::default(),
+            fumble_range: 1,
+            morale: dice("3d6"),
+            surrender_margin: 6,
+            escape_distance: 40.0,
+            high_ground: 2,
+            safe_fall: 3.0,
+            fall_damage: dice("1d6"),
+        }
+    }
+}
+
+impl Rules {
+    /// Roll `attribute` for a randomly generated player.
+    pub fn roll_attribute<R: Rng + ?Sized>(&self, attribute: Attribute, rng: &mut R) -> i32 {
+        let dice = match attribute {
+            Attribute::Speed => &self.speed,
+            _ => &self.attribute,
+        };
+        dice.roll(rng).total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_rules_keep_defaults() {
+        let rules: Rules = serde_yaml::from_str("attribute: 4d6kh3\nattack: 1d20adv\n").unwrap();
+
+        assert_eq!(rules.attribute.to_string(), "4d6kh3");
+        assert_eq!(rules.attack.to_string(), "1d20adv");
+        assert_eq!(rules.damage, Rules::default().damage);
+        assert!(serde_yaml::from_str::<Rules>("damage: 2x6\n").is_err());
+    }
+}
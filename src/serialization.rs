@@ -1,33 +1,268 @@
-use serde::Deserialize;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 use std::fs;
 use std::path::Path;
 use tracing::{error, info};
-use crate::player::{Player, Location};
+use crate::names;
+use crate::player::{Attribute, Location, Player, DEFAULT_SIZE};
+use crate::abilities::{self, AbilityCatalog};
+use crate::arena::Arena;
+use crate::behavior::BehaviorProfile;
+use crate::equipment::{EquipmentConfig, ItemCatalog};
+use crate::rules::Rules;
+use crate::targeting::Targeting;
+use crate::validation::{self, ConfigError, LineIndex, Problem};
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct LocationConfig {
     pub x: f32,
     pub y: f32,
     pub z: f32,
 }
 
-#[derive(Deserialize, Debug)]
+/// Room left between the footprints of a squad lined up by `formation`.
+const SQUAD_GAP: f32 = 1.0;
+
+/// A player as the simulation YAML describes it. The name, attributes, morale and location
+/// can be left out to have them rolled the way random players are, see `PlayerConfig::fill_in`.
+/// The `templates:` section holds these too, for players to take what they leave out from.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct PlayerConfig {
-    pub name: String,
-    pub attack: i32,
-    pub defense: i32,
-    pub armor: i32,
-    pub power: i32,
-    pub speed: i32,
-    pub range: i32,
-    pub loc: LocationConfig,
+    /// Template this one starts from, see `PlayerConfig::resolve`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// How many players this one stands for, see `PlayerConfig::squad`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub targeting: Option<Targeting>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub behavior: Option<BehaviorProfile>,
+    #[serde(default, skip_serializing_if = "EquipmentConfig::is_empty")]
+    pub equipment: EquipmentConfig,
+    /// Names from the `abilities:` catalog, tried in this order each turn.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub abilities: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attack: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub defense: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub armor: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<i32>,
+    /// Armor regained at the start of each turn.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regen: Option<i32>,
+    /// Rolled like the other attributes when left out; players with a morale of 0 never break.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub morale: Option<i32>,
+    /// How far the player's footprint reaches from where it stands.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<f32>,
+    /// Flyers ignore the ground below them and never fall.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flying: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loc: Option<LocationConfig>,
+}
+
+impl PlayerConfig {
+    /// Roll whatever was left out the way `Player::randomize` does, drawing a name that is
+    /// not among the `taken` ones.
+    pub fn fill_in<R: Rng + ?Sized>(&mut self, rules: &Rules, arena: &Arena, taken: &HashSet<String>, rng: &mut R) {
+        if self.name.is_none() {
+            self.name = Some(names::unused_name(taken, rng));
+        }
+        self.attack.get_or_insert_with(|| rules.roll_attribute(Attribute::Attack, rng));
+        self.defense.get_or_insert_with(|| rules.roll_attribute(Attribute::Defense, rng));
+        self.armor.get_or_insert_with(|| rules.roll_attribute(Attribute::Armor, rng));
+        self.power.get_or_insert_with(|| rules.roll_attribute(Attribute::Power, rng));
+        self.speed.get_or_insert_with(|| rules.roll_attribute(Attribute::Speed, rng));
+        self.range.get_or_insert_with(|| rules.roll_attribute(Attribute::Range, rng));
+        self.morale.get_or_insert_with(|| rules.roll_attribute(Attribute::Morale, rng));
+        self.loc.get_or_insert_with(|| {
+            let mut loc = Location::new(0.0, 0.0, 0.0);
+            loc.randomize(arena, rng);
+            LocationConfig::from(&loc)
+        });
+    }
+
+    /// This player with whatever it leaves out taken from its template, and from that
+    /// template's own template in turn.
+    pub fn resolve(&self, templates: &BTreeMap<String, PlayerConfig>) -> Result<PlayerConfig, TemplateError> {
+        let mut player = self.clone();
+        let mut chain: Vec<&str> = Vec::new();
+        let mut next = self.template.as_deref();
+        while let Some(name) = next {
+            if chain.contains(&name) {
+                return Err(TemplateError::Cycle(name.to_string()));
+            }
+            let template = templates.get(name).ok_or_else(|| TemplateError::Unknown(name.to_string()))?;
+            player.inherit(template);
+            chain.push(name);
+            next = template.template.as_deref();
+        }
+        Ok(player)
+    }
+
+    fn inherit(&mut self, base: &PlayerConfig) {
+        inherit(&mut self.count, &base.count);
+        inherit(&mut self.name, &base.name);
+        inherit(&mut self.team, &base.team);
+        inherit(&mut self.targeting, &base.targeting);
+        inherit(&mut self.behavior, &base.behavior);
+        inherit(&mut self.equipment.weapon, &base.equipment.weapon);
+        inherit(&mut self.equipment.armor, &base.equipment.armor);
+        if self.equipment.consumables.is_empty() {
+            self.equipment.consumables = base.equipment.consumables.clone();
+        }
+        if self.abilities.is_empty() {
+            self.abilities = base.abilities.clone();
+        }
+        inherit(&mut self.attack, &base.attack);
+        inherit(&mut self.defense, &base.defense);
+        inherit(&mut self.armor, &base.armor);
+        inherit(&mut self.power, &base.power);
+        inherit(&mut self.speed, &base.speed);
+        inherit(&mut self.range, &base.range);
+        inherit(&mut self.regen, &base.regen);
+        inherit(&mut self.morale, &base.morale);
+        inherit(&mut self.size, &base.size);
+        inherit(&mut self.flying, &base.flying);
+        inherit(&mut self.loc, &base.loc);
+    }
+
+    /// The players this one stands for: itself, or with a `count` that many copies numbered
+    /// after its name, or its template's name when it has none. Unnamed copies get rolled
+    /// names later like anyone else, placed copies line up around the `loc`, see `formation`.
+    pub fn squad(mut self, arena: &Arena) -> Vec<PlayerConfig> {
+        let count = self.count.take().unwrap_or(1);
+        if count == 1 {
+            return vec![self];
+        }
+        let base = self.name.clone().or_else(|| self.template.clone());
+        let spots = match &self.loc {
+            Some(loc) => formation(&Location::from(loc), count as usize, self.size.unwrap_or(DEFAULT_SIZE), arena),
+            None => Vec::new(),
+        };
+        (1..=count)
+            .map(|n| PlayerConfig {
+                name: base.as_ref().map(|base| format!("{} {}", base, n)),
+                loc: spots.get(n as usize - 1).map(LocationConfig::from).or_else(|| self.loc.clone()),
+                ..self.clone()
+            })
+            .collect()
+    }
+}
+
+/// Spots for `count` units of `size` around `base`, the base first and then a ring at a
+/// time outwards, skipping any out of bounds, inside a wall or behind one. Fewer come back
+/// when walls hem the squad in.
+fn formation(base: &Location, count: usize, size: f32, arena: &Arena) -> Vec<Location> {
+    let spacing = 2.0 * size + SQUAD_GAP;
+    let mut spots = Vec::new();
+    for ring in 0..=count as i32 {
+        for dx in -ring..=ring {
+            for dy in -ring..=ring {
+                if dx.abs().max(dy.abs()) != ring {
+                    continue;
+                }
+                let spot = Location::new(base.x + dx as f32 * spacing, base.y + dy as f32 * spacing, base.z);
+                if arena.check(&spot).is_ok() && !arena.blocks_sight(base, &spot) {
+                    spots.push(spot);
+                    if spots.len() == count {
+                        return spots;
+                    }
+                }
+            }
+        }
+    }
+    spots
+}
+
+fn inherit<T: Clone>(field: &mut Option<T>, base: &Option<T>) {
+    if field.is_none() {
+        *field = base.clone();
+    }
 }
 
-#[derive(Deserialize, Debug)]
+/// Why a player's template could not be resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateError {
+    Unknown(String),
+    Cycle(String),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::Unknown(name) => write!(f, "no template named '{}'", name),
+            TemplateError::Cycle(name) => write!(f, "template '{}' ends up inheriting from itself", name),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+
+/// The newest `version:` of the configuration format this build understands.
+pub const SCHEMA_VERSION: u32 = 2;
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct SimulationConfig {
+    /// Format the file is written in, the newest when left out.
+    #[serde(default)]
+    pub version: Option<u32>,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub rules: Rules,
+    /// Where the battle is fought, an empty 60 by 60 field when left out.
+    #[serde(default)]
+    pub arena: Arena,
+    /// Weapons and armor the players' `equipment` refers to.
+    #[serde(default)]
+    pub items: ItemCatalog,
+    #[serde(default)]
+    pub abilities: AbilityCatalog,
+    /// Archetypes the players can name as their `template`.
+    #[serde(default)]
+    pub templates: BTreeMap<String, PlayerConfig>,
     pub players: Vec<PlayerConfig>,
 }
 
+impl SimulationConfig {
+    /// Roll everything the players' configs leave out, keeping their names unique.
+    pub fn fill_in<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let mut taken: HashSet<String> = self.players.iter().filter_map(|p| p.name.clone()).collect();
+        for player in &mut self.players {
+            player.fill_in(&self.rules, &self.arena, &taken, rng);
+            taken.extend(player.name.clone());
+        }
+    }
+
+    /// Resolve every player's template and spread squads out into a player each. Players
+    /// whose template cannot be resolved are kept as they are, `validate` reports those.
+    pub fn expand_templates(&mut self) {
+        let players = std::mem::take(&mut self.players);
+        self.players = players
+            .into_iter()
+            .flat_map(|player| player.resolve(&self.templates).unwrap_or(player).squad(&self.arena))
+            .collect();
+    }
+}
+
 impl From<LocationConfig> for Location {
     fn from(config: LocationConfig) -> Self {
         Location::new(config.x, config.y, config.z)
@@ -40,50 +275,145 @@ impl From<&LocationConfig> for Location {
     }
 }
 
+/// Anything `fill_in` has not rolled yet comes out as zero.
 impl From<PlayerConfig> for Player {
     fn from(config: PlayerConfig) -> Self {
-        let mut player = Player::new(&config.name);
+        let mut player = Player::new(config.name.as_deref().unwrap_or_default());
+        player.team = config.team;
+        player.targeting = config.targeting.unwrap_or_default();
+        player.behavior = config.behavior.unwrap_or_default();
         
         // Set attributes with base and current values
-        player.attack.set(config.attack);
-        player.defense.set(config.defense);
-        player.armor.set(config.armor);
-        player.power.set(config.power);
-        player.speed.set(config.speed);
-        player.range.set(config.range);
+        player.attack.set(config.attack.unwrap_or_default());
+        player.defense.set(config.defense.unwrap_or_default());
+        player.armor.set(config.armor.unwrap_or_default());
+        player.power.set(config.power.unwrap_or_default());
+        player.speed.set(config.speed.unwrap_or_default());
+        player.range.set(config.range.unwrap_or_default());
+        player.regen.set(config.regen.unwrap_or(0));
+        player.morale.set(config.morale.unwrap_or(0));
+        player.size = config.size.unwrap_or(DEFAULT_SIZE);
+        player.flying = config.flying.unwrap_or(false);
         
         // Set location
-        player.loc = Location::from(&config.loc);
+        if let Some(loc) = &config.loc {
+            player.loc = Location::from(loc);
+        }
         
         player
     }
 }
 
-pub fn load_simulation_config<P: AsRef<Path>>(path: P) -> Result<SimulationConfig, Box<dyn std::error::Error>> {
+impl From<&Location> for LocationConfig {
+    fn from(loc: &Location) -> Self {
+        LocationConfig { x: loc.x, y: loc.y, z: loc.z }
+    }
+}
+
+impl From<&Player> for PlayerConfig {
+    fn from(player: &Player) -> Self {
+        PlayerConfig {
+            template: None,
+            count: None,
+            name: Some(player.name.clone()),
+            team: player.team.clone(),
+            targeting: (!player.targeting.is_default()).then_some(player.targeting),
+            behavior: (!player.behavior.is_default()).then_some(player.behavior),
+            equipment: EquipmentConfig::from(&player.equipment),
+            abilities: player.abilities.iter().map(|slot| slot.name.clone()).collect(),
+            attack: Some(player.attack.base),
+            defense: Some(player.defense.base),
+            armor: Some(player.armor.base),
+            power: Some(player.power.base),
+            speed: Some(player.speed.base),
+            range: Some(player.range.base),
+            regen: (player.regen.base != 0).then_some(player.regen.base),
+            morale: Some(player.morale.base),
+            size: (player.size != DEFAULT_SIZE).then_some(player.size),
+            flying: player.flying.then_some(true),
+            loc: Some(LocationConfig::from(&player.loc)),
+        }
+    }
+}
+
+/// Load the simulation configuration at `path`, reporting every problem in it at once.
+pub fn load_simulation_config<P: AsRef<Path>>(path: P) -> Result<SimulationConfig, ConfigError> {
     let path = path.as_ref();
     info!("Loading simulation configuration from: {}", path.display());
-    
-    let content = fs::read_to_string(path)
-        .map_err(|e| {
-            error!("Failed to read file {}: {}", path.display(), e);
-            e
-        })?;
-    
-    let config: SimulationConfig = serde_yaml::from_str(&content)
+    let fail = |problems| ConfigError {
+        path: path.display().to_string(),
+        problems,
+    };
+
+    let content = fs::read_to_string(path).map_err(|e| {
+        fail(vec![Problem {
+            line: None,
+            field: String::new(),
+            message: e.to_string(),
+        }])
+    })?;
+    let config = parse_simulation_config(&content).map_err(fail)?;
+
+    info!("Successfully loaded {} players from configuration", config.players.len());
+    Ok(config)
+}
+
+/// Parse a simulation configuration and validate it, keys it does not know included. The
+/// players come back with their templates resolved and squads spread out.
+pub fn parse_simulation_config(content: &str) -> Result<SimulationConfig, Vec<Problem>> {
+    let mut unknown = Vec::new();
+    let mut config: SimulationConfig =
+        serde_ignored::deserialize(serde_yaml::Deserializer::from_str(content), |path| {
+            unknown.push(validation::field_path(&path))
+        })
         .map_err(|e| {
-            error!("Failed to parse YAML from {}: {}", path.display(), e);
-            e
+            vec![Problem {
+                line: None,
+                field: String::new(),
+                message: e.to_string(),
+            }]
         })?;
-    
-    info!("Successfully loaded {} players from configuration", config.players.len());
+
+    let lines = LineIndex::new(content);
+    let mut problems: Vec<Problem> = unknown
+        .into_iter()
+        .map(|field| lines.problem(field, "unknown field"))
+        .collect();
+    problems.extend(validation::validate(&config, &lines));
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+    config.expand_templates();
     Ok(config)
 }
 
-pub fn players_from_config(config: SimulationConfig) -> Vec<Player> {
+/// Build a player and hand it the items and abilities its config refers to.
+pub fn player_from_config(config: PlayerConfig, items: &ItemCatalog, catalog: &AbilityCatalog) -> Player {
+    let equipment = items.equip(&config.equipment);
+    let known = abilities::learn(catalog, &config.abilities);
+    let mut player = Player::from(config);
+    player.equipment = equipment.unwrap_or_else(|e| {
+        error!("Player {} goes unequipped: {}", player.name, e);
+        Default::default()
+    });
+    player.abilities = known.unwrap_or_else(|e| {
+        error!("Player {} fights without abilities: {}", player.name, e);
+        Vec::new()
+    });
+    player
+}
+
+/// Build the configured roster, rolling whatever the configuration leaves out.
+pub fn players_from_config<R: Rng + ?Sized>(mut config: SimulationConfig, rng: &mut R) -> Vec<Player> {
+    config.fill_in(rng);
+    let items = config.items;
+    let catalog = config.abilities;
+    let arena = config.arena;
     config.players
         .into_iter()
         .map(|player_config| {
-            let player = Player::from(player_config);
+            let mut player = player_from_config(player_config, &items, &catalog);
+            player.loc = arena.landing(&player.loc, player.flying);
             info!("{:?}", player);
             player
         })
@@ -107,19 +437,31 @@ mod tests {
     #[test]
     fn test_player_conversion() {
         let player_config = PlayerConfig {
-            name: "Test Player".to_string(),
-            attack: 10,
-            defense: 12,
-            armor: 14,
-            power: 8,
-            speed: 16,
-            range: 6,
-            loc: LocationConfig { x: 5.0, y: 10.0, z: 0.0 },
+            template: None,
+            count: None,
+            name: Some("Test Player".to_string()),
+            team: Some("Red".to_string()),
+            targeting: Some(Targeting::Weakest),
+            behavior: Some(BehaviorProfile::Kiting),
+            equipment: EquipmentConfig::default(),
+            abilities: Vec::new(),
+            attack: Some(10),
+            defense: Some(12),
+            armor: Some(14),
+            power: Some(8),
+            speed: Some(16),
+            range: Some(6),
+            regen: None,
+            morale: None,
+            size: None,
+            flying: None,
+            loc: Some(LocationConfig { x: 5.0, y: 10.0, z: 0.0 }),
         };
         
         let player: Player = player_config.into();
         
         assert_eq!(player.name, "Test Player");
+        assert_eq!(player.side(), "Red");
         assert_eq!(player.attack.base, 10);
         assert_eq!(player.defense.base, 12);
         assert_eq!(player.armor.base, 14);
@@ -130,4 +472,74 @@ mod tests {
         assert_eq!(player.loc.y, 10.0);
         assert_eq!(player.loc.z, 0.0);
     }
+
+    #[test]
+    fn test_missing_fields_are_rolled() {
+        let config = parse_simulation_config("players:\n  - name: Fred\n    speed: 30\n  - {}\n  - {}\n").unwrap();
+        let players = players_from_config(config, &mut crate::dice::seeded_rng(4));
+
+        assert_eq!(players[0].name, "Fred");
+        assert_eq!(players[0].speed.base, 30);
+        assert!((3..=18).contains(&players[0].attack.base));
+        assert!((11..=18).contains(&players[1].speed.base));
+        assert!(!players[1].is_fearless());
+        assert!((3..=18).contains(&players[1].morale.base));
+        assert!(!players[2].name.is_empty() && players[2].name != players[1].name);
+        assert!(players.iter().all(|p| Arena::default().in_bounds(&p.loc)));
+    }
+
+    #[test]
+    fn test_squads_spread_out() {
+        let mut arena = Arena::new(20.0, 20.0);
+        arena.walls.push(crate::arena::Region { x: 11.5, y: 0.0, width: 1.0, height: 20.0 });
+        let squad = PlayerConfig {
+            name: Some("g".to_string()),
+            count: Some(9),
+            loc: Some(LocationConfig { x: 10.0, y: 10.0, z: 0.0 }),
+            ..Default::default()
+        }
+        .squad(&arena);
+
+        assert_eq!(squad.len(), 9);
+        let spots: Vec<Location> = squad.iter().map(|p| Location::from(p.loc.as_ref().unwrap())).collect();
+        assert_eq!(spots[0], Location::new(10.0, 10.0, 0.0));
+        for (i, a) in spots.iter().enumerate() {
+            assert!(a.x < 11.5, "{:?} is past the wall", a);
+            assert!(spots[i + 1..].iter().all(|b| a.distance(b) >= 2.0 * DEFAULT_SIZE));
+        }
+    }
+
+    #[test]
+    fn test_templates_and_squads() {
+        let config = parse_simulation_config(
+            "
+templates:
+  soldier:
+    attack: 12
+    defense: 14
+    team: Blue
+  archer:
+    template: soldier
+    behavior: kiting
+    range: 18
+players:
+  - template: archer
+    count: 3
+    defense: 8
+  - name: Captain
+    template: soldier
+    attack: 16
+",
+        )
+        .unwrap();
+
+        let names: Vec<_> = config.players.iter().map(|p| p.name.as_deref().unwrap()).collect();
+        assert_eq!(names, ["archer 1", "archer 2", "archer 3", "Captain"]);
+        let archer = &config.players[2];
+        assert_eq!((archer.attack, archer.defense, archer.range), (Some(12), Some(8), Some(18)));
+        assert_eq!(archer.behavior, Some(BehaviorProfile::Kiting));
+        assert_eq!(archer.team.as_deref(), Some("Blue"));
+        let captain = &config.players[3];
+        assert_eq!((captain.attack, captain.defense, captain.behavior), (Some(16), Some(14), None));
+    }
 }
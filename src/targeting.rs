@@ -0,0 +1,164 @@
+use rand::RngCore;
+use rand::seq::IteratorRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::player::Player;
+
+/// Picks which enemy a player goes after this turn.
+pub trait TargetingStrategy {
+    /// Returns the index in `players` of the chosen target, `focus` is the target the
+    /// player's team agreed on, if any.
+    fn select(
+        &self,
+        source: &Player,
+        players: &VecDeque<Player>,
+        focus: Option<&str>,
+        rng: &mut dyn RngCore,
+    ) -> Option<usize>;
+}
+
+/// The targeting strategies players can choose from in the simulation YAML.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Targeting {
+    #[default]
+    Nearest,
+    Weakest,
+    StrongestThreat,
+    LowestDefense,
+    Random,
+    FocusFire,
+}
+
+impl Targeting {
+    pub fn strategy(&self) -> &'static dyn TargetingStrategy {
+        match self {
+            Targeting::Nearest => &Nearest,
+            Targeting::Weakest => &Weakest,
+            Targeting::StrongestThreat => &StrongestThreat,
+            Targeting::LowestDefense => &LowestDefense,
+            Targeting::Random => &RandomTarget,
+            Targeting::FocusFire => &FocusFire,
+        }
+    }
+    pub fn is_default(&self) -> bool {
+        *self == Targeting::default()
+    }
+}
+
+fn enemies<'a>(
+    source: &'a Player,
+    players: &'a VecDeque<Player>,
+) -> impl Iterator<Item = (usize, &'a Player)> + 'a {
+    players
+        .iter()
+        .enumerate()
+        .filter(move |(_, player)| !source.is_ally(player))
+}
+
+/// The enemy with the lowest `key`, ties going to the closest one.
+fn lowest_by(source: &Player, players: &VecDeque<Player>, key: impl Fn(&Player) -> i32) -> Option<usize> {
+    enemies(source, players)
+        .min_by(|(_, a), (_, b)| {
+            key(a).cmp(&key(b)).then_with(|| {
+                source
+                    .loc
+                    .distance(&a.loc)
+                    .total_cmp(&source.loc.distance(&b.loc))
+            })
+        })
+        .map(|(idx, _)| idx)
+}
+
+pub struct Nearest;
+
+impl TargetingStrategy for Nearest {
+    fn select(&self, source: &Player, players: &VecDeque<Player>, _: Option<&str>, _: &mut dyn RngCore) -> Option<usize> {
+        lowest_by(source, players, |_| 0)
+    }
+}
+
+/// Goes after the enemy with the least armor left.
+pub struct Weakest;
+
+impl TargetingStrategy for Weakest {
+    fn select(&self, source: &Player, players: &VecDeque<Player>, _: Option<&str>, _: &mut dyn RngCore) -> Option<usize> {
+        lowest_by(source, players, |p| p.armor.curr)
+    }
+}
+
+/// Goes after the enemy that hits the hardest.
+pub struct StrongestThreat;
+
+impl TargetingStrategy for StrongestThreat {
+    fn select(&self, source: &Player, players: &VecDeque<Player>, _: Option<&str>, _: &mut dyn RngCore) -> Option<usize> {
+        lowest_by(source, players, |p| -(p.attack.bonus() + p.power.bonus()))
+    }
+}
+
+/// Goes after the enemy that is easiest to hit.
+pub struct LowestDefense;
+
+impl TargetingStrategy for LowestDefense {
+    fn select(&self, source: &Player, players: &VecDeque<Player>, _: Option<&str>, _: &mut dyn RngCore) -> Option<usize> {
+        lowest_by(source, players, |p| p.effective_defense())
+    }
+}
+
+pub struct RandomTarget;
+
+impl TargetingStrategy for RandomTarget {
+    fn select(&self, source: &Player, players: &VecDeque<Player>, _: Option<&str>, rng: &mut dyn RngCore) -> Option<usize> {
+        enemies(source, players).choose(rng).map(|(idx, _)| idx)
+    }
+}
+
+/// Sticks with the team's chosen target while it stands, otherwise picks the nearest.
+pub struct FocusFire;
+
+impl TargetingStrategy for FocusFire {
+    fn select(&self, source: &Player, players: &VecDeque<Player>, focus: Option<&str>, rng: &mut dyn RngCore) -> Option<usize> {
+        focus
+            .and_then(|focus| enemies(source, players).find(|(_, p)| p.name == focus))
+            .map(|(idx, _)| idx)
+            .or_else(|| Nearest.select(source, players, None, rng))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roster() -> VecDeque<Player> {
+        let mut players = VecDeque::new();
+        for (name, x, armor, defense) in [("near", 1.0, 12, 15), ("weak", 5.0, 3, 14), ("soft", 9.0, 10, 4)] {
+            let mut player = Player::new(name);
+            player.loc.x = x;
+            player.armor.set(armor);
+            player.defense.set(defense);
+            players.push_back(player);
+        }
+        players
+    }
+
+    #[test]
+    fn test_strategies_pick_expected_targets() {
+        let source = Player::new("source");
+        let players = roster();
+        let mut rng = crate::dice::seeded_rng(1);
+        let pick = |targeting: Targeting, focus, rng: &mut dyn RngCore| {
+            targeting
+                .strategy()
+                .select(&source, &players, focus, rng)
+                .map(|idx| players[idx].name.as_str())
+        };
+
+        assert_eq!(pick(Targeting::Nearest, None, &mut rng), Some("near"));
+        assert_eq!(pick(Targeting::Weakest, None, &mut rng), Some("weak"));
+        assert_eq!(pick(Targeting::LowestDefense, None, &mut rng), Some("soft"));
+        assert_eq!(pick(Targeting::FocusFire, Some("soft"), &mut rng), Some("soft"));
+        assert_eq!(pick(Targeting::FocusFire, Some("gone"), &mut rng), Some("near"));
+        assert!(pick(Targeting::Random, None, &mut rng).is_some());
+    }
+}
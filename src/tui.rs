@@ -10,6 +10,7 @@ use crossterm::{
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use rand::Rng;
 use ratatui::{
     backend::{Backend, CrosstermBackend},
     layout::{Alignment, Constraint, Direction, Layout, Rect},
@@ -22,6 +23,8 @@ use ratatui::{
 };
 
 use crate::app::{App, AppState, BattleEventType};
+use crate::arena::Terrain;
+use crate::player::Location;
 
 pub struct Tui<B: Backend> {
     terminal: Terminal<B>,
@@ -36,43 +39,39 @@ impl<B: Backend> Tui<B> {
         }
     }
 
-    pub fn run(&mut self, mut app: App) -> io::Result<()> {
-        let mut rng = rand::rng();
-
+    pub fn run<R: Rng>(&mut self, mut app: App, rng: &mut R) -> io::Result<()> {
         loop {
             self.terminal.draw(|f| Self::render_static(f, &app))?;
 
             let timeout = Duration::from_millis(app.tick_rate);
-            if crossterm::event::poll(timeout)? {
-                if let Event::Key(key) = event::read()? {
-                    match key.code {
-                        KeyCode::Char('q') => {
-                            app.quit();
-                        }
-                        KeyCode::Char(' ') => {
-                            match app.state {
-                                AppState::Setup => app.start_battle(),
-                                AppState::Running | AppState::Paused => app.toggle_pause(),
-                                AppState::Finished => app.quit(),
-                                _ => {}
-                            }
-                        }
-                        KeyCode::Char('s') => {
-                            if app.state == AppState::Paused || app.state == AppState::Running {
-                                app.step_battle(&mut rng);
-                            }
-                        }
-                        KeyCode::Char('a') => {
-                            app.toggle_auto_advance();
-                        }
-                        KeyCode::Char('r') => {
-                            if app.state == AppState::Finished {
-                                // Reset the app for a new battle
-                                // Note: This would need the original player setup logic
-                            }
+            if crossterm::event::poll(timeout)?
+                && let Event::Key(key) = event::read()?
+            {
+                match key.code {
+                    KeyCode::Char('q') => {
+                        app.quit();
+                    }
+                    KeyCode::Char(' ') => {
+                        match app.state {
+                            AppState::Setup => app.start_battle(),
+                            AppState::Running | AppState::Paused => app.toggle_pause(),
+                            AppState::Finished => app.quit(),
+                            _ => {}
                         }
-                        _ => {}
                     }
+                    KeyCode::Char('s')
+                        if app.state == AppState::Paused || app.state == AppState::Running =>
+                    {
+                        app.step_battle(rng);
+                    }
+                    KeyCode::Char('a') => {
+                        app.toggle_auto_advance();
+                    }
+                    KeyCode::Char('r') if app.state == AppState::Finished => {
+                        // Reset the app for a new battle
+                        // Note: This would need the original player setup logic
+                    }
+                    _ => {}
                 }
             }
 
@@ -80,7 +79,7 @@ impl<B: Backend> Tui<B> {
             if app.auto_advance && app.state == AppState::Running {
                 let now = Instant::now();
                 if now.duration_since(self.last_tick) >= Duration::from_millis(app.tick_rate) {
-                    app.step_battle(&mut rng);
+                    app.step_battle(rng);
                     self.last_tick = now;
                 }
             }
@@ -139,9 +138,16 @@ impl<B: Backend> Tui<B> {
             .style(Style::default().fg(Color::Cyan))
             .title(title);
 
-        let title_paragraph = Paragraph::new(format!("Turn: {} | Players: {}", 
-                                                    app.current_turn, 
-                                                    app.game.players.len()))
+        let mut title_text = format!("Round: {} | Players: {}",
+                                     app.game.turns,
+                                     app.game.players.len());
+        if let Some(seed) = app.seed {
+            title_text.push_str(&format!(" | Seed: {}", seed));
+        }
+        if app.playback.is_some() {
+            title_text.push_str(" | Replay");
+        }
+        let title_paragraph = Paragraph::new(title_text)
             .block(title_block)
             .alignment(Alignment::Center);
 
@@ -205,29 +211,52 @@ impl<B: Backend> Tui<B> {
         let inner_area = arena_block.inner(area);
         f.render_widget(arena_block, area);
 
-        // Draw arena background
-        let arena_width = inner_area.width as f32;
-        let arena_height = inner_area.height as f32;
-        
-        // Calculate scale based on player positions
-        let (min_x, max_x, min_y, max_y) = app.game.players.iter()
-            .fold((60.0f32, 0.0f32, 60.0f32, 0.0f32), |(min_x, max_x, min_y, max_y), player| {
-                (
-                    min_x.min(player.loc.x),
-                    max_x.max(player.loc.x),
-                    min_y.min(player.loc.y),
-                    max_y.max(player.loc.y),
-                )
-            });
-
-        let scale_x = if max_x > min_x { (arena_width - 2.0) / (max_x - min_x) } else { 1.0 };
-        let scale_y = if max_y > min_y { (arena_height - 2.0) / (max_y - min_y) } else { 1.0 };
-        let scale = scale_x.min(scale_y).min(1.0);
-
-        // Draw players on the arena
-        for (i, player) in app.game.players.iter().enumerate() {
-            let screen_x = ((player.loc.x - min_x) * scale + 1.0) as u16;
-            let screen_y = ((player.loc.y - min_y) * scale + 1.0) as u16;
+        // Fit the whole arena in, terminal cells being about twice as tall as wide
+        let arena = &app.game.arena;
+        let scale_x = inner_area.width.saturating_sub(1) as f32 / (2.0 * arena.width.max(1.0));
+        let scale_y = inner_area.height.saturating_sub(1) as f32 / arena.height.max(1.0);
+        let scale = scale_x.min(scale_y);
+
+        // Draw the terrain, each cell showing the ground at its top left corner
+        let terrain: Vec<Line> = (0..inner_area.height)
+            .map(|row| {
+                let cells: Vec<Span> = (0..inner_area.width)
+                    .map(|col| {
+                        let loc = Location::new(col as f32 / (2.0 * scale), row as f32 / scale, 0.0);
+                        match arena.terrain_at(&loc) {
+                            Terrain::Open if arena.ground_at(&loc) > 0.0 => height_cell(arena.ground_at(&loc)),
+                            terrain => terrain_cell(terrain),
+                        }
+                    })
+                    .collect();
+                Line::from(cells)
+            })
+            .collect();
+        f.render_widget(Paragraph::new(terrain), inner_area);
+
+        // Draw the routes the players are following
+        for player in app.game.players.iter() {
+            let style = Style::default().fg(team_color(app.team_index(player)));
+            for waypoint in &player.route {
+                let screen_x = (waypoint.x * 2.0 * scale).round() as u16;
+                let screen_y = (waypoint.y * scale).round() as u16;
+                if screen_x < inner_area.width && screen_y < inner_area.height {
+                    let waypoint_area = Rect {
+                        x: inner_area.x + screen_x,
+                        y: inner_area.y + screen_y,
+                        width: 1,
+                        height: 1,
+                    };
+                    f.render_widget(Paragraph::new("∙").style(style), waypoint_area);
+                }
+            }
+        }
+
+        // Draw players on the arena, the fallen stay where they dropped
+        for player in app.game.players.iter().chain(app.game.fallen.iter()) {
+            let team = app.team_index(player);
+            let screen_x = (player.loc.x * 2.0 * scale).round() as u16;
+            let screen_y = (player.loc.y * scale).round() as u16;
             
             if screen_x < inner_area.width && screen_y < inner_area.height {
                 let player_area = Rect {
@@ -240,25 +269,13 @@ impl<B: Backend> Tui<B> {
                 let player_char = if player.is_dead() { 
                     "✗" 
                 } else { 
-                    match i {
-                        0 => "●",
-                        1 => "■",
-                        2 => "▲",
-                        3 => "♦",
-                        _ => "○",
-                    }
+                    team_marker(team)
                 };
 
                 let player_color = if player.is_dead() {
                     Color::DarkGray
                 } else {
-                    match i {
-                        0 => Color::Red,
-                        1 => Color::Blue,
-                        2 => Color::Green,
-                        3 => Color::Yellow,
-                        _ => Color::Magenta,
-                    }
+                    team_color(team)
                 };
 
                 let player_widget = Paragraph::new(player_char)
@@ -278,29 +295,33 @@ impl<B: Backend> Tui<B> {
         f.render_widget(stats_block, area);
 
         let mut stats_items = Vec::new();
-        for (i, player) in app.game.players.iter().enumerate() {
+        for player in app.game.players.iter().chain(&app.game.fallen).chain(&app.game.withdrawn) {
             let health_percentage = if player.armor.base > 0 {
                 (player.armor.curr as f64 / player.armor.base as f64).max(0.0)
             } else {
                 0.0
             };
 
-            let status = if player.is_dead() { " [DEAD]" } else { "" };
-            
-            let player_color = match i {
-                0 => Color::Red,
-                1 => Color::Blue,
-                2 => Color::Green,
-                3 => Color::Yellow,
-                _ => Color::Magenta,
+            let status = if player.is_dead() {
+                " [DEAD]".to_string()
+            } else if player.is_steady() {
+                String::new()
+            } else {
+                format!(" [{}]", player.morale_state.to_string().to_uppercase())
             };
+            
+            let player_color = team_color(app.team_index(player));
 
-            let player_info = Line::from(vec![
+            let mut player_spans = vec![
                 Span::styled(
                     format!("{}{}", player.name, status),
                     Style::default().fg(player_color).add_modifier(Modifier::BOLD)
                 ),
-            ]);
+            ];
+            if let Some(team) = &player.team {
+                player_spans.push(Span::styled(format!(" ({})", team), Style::default().fg(player_color)));
+            }
+            let player_info = Line::from(player_spans);
 
             stats_items.push(ListItem::new(player_info));
 
@@ -320,13 +341,54 @@ impl<B: Backend> Tui<B> {
                     (health_percentage * 100.0) as u8));
                 stats_items.push(ListItem::new(health_info));
 
-                let attack_info = Line::from(format!("  ATK:{} DEF:{} PWR:{} SPD:{} RNG:{}", 
+                let attack_info = Line::from(format!("  ATK:{} DEF:{} PWR:{} SPD:{} RNG:{}{}", 
                     player.attack.curr, 
-                    player.defense.curr, 
+                    player.effective_defense(),
                     player.power.curr,
                     player.speed.curr,
-                    player.range.curr));
+                    player.effective_range(),
+                    if player.regen.curr > 0 { format!(" REG:{}", player.regen.curr) } else { String::new() }));
                 stats_items.push(ListItem::new(attack_info));
+
+                if player.flying || player.loc.z != 0.0 {
+                    let flying = if player.flying { " (flying)" } else { "" };
+                    stats_items.push(ListItem::new(Line::from(format!("  Height: {:.1}{}", player.loc.z, flying))));
+                }
+
+                let weapon = player.equipment.weapon.as_ref().map(|(name, _)| name.as_str());
+                let armor = player.equipment.armor.as_ref().map(|(name, _)| name.as_str());
+                let consumables = player
+                    .equipment
+                    .consumables
+                    .iter()
+                    .map(|slot| format!("{} x{}", slot.name, slot.charges));
+                let gear: Vec<String> = weapon
+                    .into_iter()
+                    .chain(armor)
+                    .map(String::from)
+                    .chain(consumables)
+                    .collect();
+                if !gear.is_empty() {
+                    stats_items.push(ListItem::new(Line::from(format!("  Gear: {}", gear.join(", ")))));
+                }
+                if !player.abilities.is_empty() {
+                    let abilities: Vec<String> = player
+                        .abilities
+                        .iter()
+                        .map(|slot| match slot.cooldown {
+                            0 => slot.name.clone(),
+                            cooldown => format!("{} ({})", slot.name, cooldown),
+                        })
+                        .collect();
+                    stats_items.push(ListItem::new(Line::from(format!("  Abilities: {}", abilities.join(", ")))));
+                }
+                if !player.effects.is_empty() {
+                    let effects: Vec<String> = player.effects.iter().map(|e| e.to_string()).collect();
+                    stats_items.push(ListItem::new(Line::from(Span::styled(
+                        format!("  Effects: {}", effects.join(", ")),
+                        Style::default().fg(Color::LightMagenta),
+                    ))));
+                }
             }
         }
 
@@ -357,6 +419,12 @@ impl<B: Backend> Tui<B> {
                     BattleEventType::Movement => Style::default().fg(Color::Cyan),
                     BattleEventType::Info => Style::default().fg(Color::White),
                     BattleEventType::Attack => Style::default().fg(Color::Green),
+                    BattleEventType::Critical => Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD),
+                    BattleEventType::Ability => Style::default().fg(Color::LightBlue).add_modifier(Modifier::BOLD),
+                    BattleEventType::Morale => Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                    BattleEventType::Heal => Style::default().fg(Color::LightGreen),
+                    BattleEventType::Effect => Style::default().fg(Color::LightMagenta),
+                    BattleEventType::Fumble => Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
                 };
 
                 let line = Line::from(vec![
@@ -387,7 +455,7 @@ impl<B: Backend> Tui<B> {
             AppState::Paused => "SPACE: Resume | s: Step | a: Toggle Auto | q: Quit [PAUSED]",
             AppState::Finished => {
                 if let Some(winner) = app.get_winner() {
-                    &format!("Winner: {} | SPACE or q: Quit", winner.name)
+                    &format!("Winner: {} | SPACE or q: Quit", winner)
                 } else {
                     "Battle ended inconclusively | SPACE or q: Quit"
                 }
@@ -427,8 +495,11 @@ impl<B: Backend> Tui<B> {
             Line::from("  q     - Quit"),
             Line::from(""),
             Line::from("Arena Symbols:"),
-            Line::from("  ● ■ ▲ ♦ - Living Players"),
+            Line::from("  ● ■ ▲ ♦ - Living Players, one per team"),
             Line::from("  ✗         - Dead Players"),
+            Line::from("  █ ~ ▒     - Walls, Difficult Terrain, Cover"),
+            Line::from("  1-9       - Raised Ground, by Height"),
+            Line::from("  ∙         - Routes Players Are Following"),
         ];
 
         let help_block = Block::default()
@@ -444,6 +515,41 @@ impl<B: Backend> Tui<B> {
     }
 }
 
+fn terrain_cell(terrain: Terrain) -> Span<'static> {
+    match terrain {
+        Terrain::Open => Span::styled("·", Style::default().fg(Color::DarkGray)),
+        Terrain::Difficult => Span::styled("~", Style::default().fg(Color::Yellow)),
+        Terrain::Cover(_) => Span::styled("▒", Style::default().fg(Color::Green)),
+        Terrain::Wall => Span::styled("█", Style::default().fg(Color::Gray)),
+        Terrain::Outside => Span::raw(" "),
+    }
+}
+
+fn height_cell(height: f32) -> Span<'static> {
+    let digit = (height.round() as u32).clamp(1, 9);
+    Span::styled(digit.to_string(), Style::default().fg(Color::DarkGray))
+}
+
+fn team_marker(team: usize) -> &'static str {
+    match team {
+        0 => "●",
+        1 => "■",
+        2 => "▲",
+        3 => "♦",
+        _ => "○",
+    }
+}
+
+fn team_color(team: usize) -> Color {
+    match team {
+        0 => Color::Red,
+        1 => Color::Blue,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        _ => Color::Magenta,
+    }
+}
+
 pub fn setup_terminal() -> io::Result<Terminal<CrosstermBackend<io::Stdout>>> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
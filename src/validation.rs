@@ -0,0 +1,436 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::abilities;
+use crate::arena::Footprint;
+use crate::player::{Location, DEFAULT_SIZE};
+use crate::serialization::{PlayerConfig, SimulationConfig, TemplateError, SCHEMA_VERSION};
+
+/// Attack, defense, power and range have to lie in here, well past anything the dice roll.
+const ATTRIBUTE_RANGE: RangeInclusive<i32> = 0..=100;
+/// Armor and speed too, but a player without either could never fight.
+const VITAL_RANGE: RangeInclusive<i32> = 1..=100;
+/// A squad's `count`, as many players as a random roster can have at most.
+const SQUAD_RANGE: RangeInclusive<u32> = 1..=64;
+
+/// One thing wrong with a configuration, and where.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub line: Option<usize>,
+    /// Path to the offending field, such as `players[1].speed`.
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "{}: ", line)?;
+        }
+        if !self.field.is_empty() {
+            write!(f, "{}: ", self.field)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Everything wrong with the configuration file at `path`.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: String,
+    pub problems: Vec<Problem>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self
+            .problems
+            .iter()
+            .map(|p| match p.line {
+                Some(_) => format!("{}:{}", self.path, p),
+                None => format!("{}: {}", self.path, p),
+            })
+            .collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// The line each field of a block style YAML document starts on, so problems found after
+/// parsing can still point into the file. Fields inside flow style `{ ... }` maps share
+/// the line of the map.
+pub struct LineIndex {
+    lines: HashMap<String, usize>,
+}
+
+impl LineIndex {
+    pub fn new(content: &str) -> Self {
+        let mut lines = HashMap::new();
+        let mut items: HashMap<String, usize> = HashMap::new();
+        // the fields still open, with the column their key starts in
+        let mut open: Vec<(usize, String)> = Vec::new();
+        for (number, text) in content.lines().enumerate() {
+            let mut rest = text.trim_start();
+            if rest.is_empty() || rest.starts_with('#') || rest.starts_with("---") {
+                continue;
+            }
+            let column = text.len() - rest.len();
+            let item = rest == "-" || rest.starts_with("- ");
+            while let Some((indent, path)) = open.last() {
+                // a list may sit at the same column as its key
+                if *indent > column || (*indent == column && (!item || path.ends_with(']'))) {
+                    open.pop();
+                } else {
+                    break;
+                }
+            }
+            let mut parent = open.last().map(|(_, path)| path.clone()).unwrap_or_default();
+            let mut column = column;
+            if item {
+                let index = items.entry(parent.clone()).or_insert(0);
+                parent = format!("{}[{}]", parent, index);
+                *index += 1;
+                lines.insert(parent.clone(), number + 1);
+                open.push((column, parent.clone()));
+                rest = rest[1..].trim_start();
+                column = text.len() - rest.len();
+            }
+            if let Some(key) = key_of(rest) {
+                let path = if parent.is_empty() { key.to_string() } else { format!("{}.{}", parent, key) };
+                lines.insert(path.clone(), number + 1);
+                open.push((column, path));
+            }
+        }
+        LineIndex { lines }
+    }
+    /// The line `field` starts on, or failing that the closest enclosing field's.
+    pub fn line(&self, field: &str) -> Option<usize> {
+        let mut field = field;
+        loop {
+            if let Some(&line) = self.lines.get(field) {
+                return Some(line);
+            }
+            field = &field[..field.rfind(['.', '['])?];
+        }
+    }
+    pub fn problem(&self, field: String, message: impl fmt::Display) -> Problem {
+        Problem {
+            line: self.line(&field),
+            field,
+            message: message.to_string(),
+        }
+    }
+}
+
+fn key_of(text: &str) -> Option<&str> {
+    if text.starts_with(['{', '[', '"', '\'']) {
+        return None;
+    }
+    let end = text.find(": ").or_else(|| text.strip_suffix(':').map(str::len))?;
+    Some(text[..end].trim())
+}
+
+/// Write a path to an ignored field the way serde_yaml reports fields, `players[0].loc.x`.
+pub fn field_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, index } => format!("{}[{}]", field_path(parent), index),
+        Path::Map { parent, key } => match field_path(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{}.{}", parent, key),
+        },
+        Path::Some { parent } | Path::NewtypeStruct { parent } | Path::NewtypeVariant { parent } => field_path(parent),
+    }
+}
+
+/// Check a parsed configuration for everything the types alone cannot rule out, reporting
+/// each problem with the line of `lines` it is on.
+pub fn validate(config: &SimulationConfig, lines: &LineIndex) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    if let Some(version) = config.version.filter(|&version| version > SCHEMA_VERSION) {
+        problems.push(lines.problem(
+            "version".to_string(),
+            format!("{} is newer than the supported version {}", version, SCHEMA_VERSION),
+        ));
+    }
+    let arena = &config.arena;
+    if !(arena.width > 0.0 && arena.width.is_finite() && arena.height > 0.0 && arena.height.is_finite()) {
+        problems.push(lines.problem(
+            "arena".to_string(),
+            format!("{} by {} is no size for an arena", arena.width, arena.height),
+        ));
+    }
+    if config.players.is_empty() {
+        problems.push(lines.problem("players".to_string(), "nobody to fight, the roster is empty"));
+    }
+    for (key, template) in &config.templates {
+        let field = |name: &str| format!("templates.{}.{}", key, name);
+        if let Err(e) = template.resolve(&config.templates) {
+            problems.push(lines.problem(field("template"), e));
+        }
+        problems.extend(check_player(config, template, &field, lines));
+    }
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    // where everybody placed so far starts, and the entries already reported for overlapping
+    let mut placed: Vec<(usize, Footprint)> = Vec::new();
+    let mut crowded = HashSet::new();
+    for (i, player) in config.players.iter().enumerate() {
+        let field = |name: &str| format!("players[{}].{}", i, name);
+        // a broken template chain is reported with the templates
+        if let Some(name) = player.template.as_ref().filter(|name| !config.templates.contains_key(*name)) {
+            problems.push(lines.problem(field("template"), TemplateError::Unknown(name.clone())));
+        }
+        let resolved = player.resolve(&config.templates).unwrap_or_else(|_| player.clone());
+        // an out of range count is reported below, spreading it out could take forever
+        let squad = match resolved.count {
+            Some(count) if !SQUAD_RANGE.contains(&count) => vec![resolved],
+            _ => resolved.squad(arena),
+        };
+        for member in &squad {
+            let Some(name) = &member.name else { continue };
+            let first = *seen.entry(name.clone()).or_insert(i);
+            if first != i {
+                problems.push(lines.problem(
+                    field("name"),
+                    format!("{} is already the name of players[{}]", name, first),
+                ));
+            }
+        }
+        for member in &squad {
+            let Some(loc) = member.loc.as_ref().map(Location::from) else { continue };
+            let size = member.size.unwrap_or(DEFAULT_SIZE);
+            if ![loc.x, loc.y, loc.z, size].iter().all(|value| value.is_finite()) {
+                continue;
+            }
+            if let Some((other, _)) = placed.iter().find(|(_, unit)| unit.overlaps(&loc, size))
+                && crowded.insert(i)
+            {
+                let message = if *other == i {
+                    "there is no room to spread the squad out here".to_string()
+                } else {
+                    format!("starts on top of players[{}]", other)
+                };
+                problems.push(lines.problem(field("loc"), message));
+            }
+            placed.push((i, Footprint { loc, size, hostile: false }));
+        }
+        problems.extend(check_player(config, player, &field, lines));
+    }
+    problems
+}
+
+/// Check the fields a player or template gives itself, naming them with `field`.
+fn check_player(config: &SimulationConfig, player: &PlayerConfig, field: &dyn Fn(&str) -> String, lines: &LineIndex) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    if let Some(count) = player.count.filter(|count| !SQUAD_RANGE.contains(count)) {
+        problems.push(lines.problem(
+            field("count"),
+            format!("{} is out of range {}..={}", count, SQUAD_RANGE.start(), SQUAD_RANGE.end()),
+        ));
+    }
+    for (name, value, range) in attributes(player) {
+        if !range.contains(&value) {
+            problems.push(lines.problem(
+                field(name),
+                format!("{} is out of range {}..={}", value, range.start(), range.end()),
+            ));
+        }
+    }
+    if let Some(size) = player.size
+        && !(size > 0.0 && size.is_finite())
+    {
+        problems.push(lines.problem(field("size"), format!("{} is no size for a player", size)));
+    }
+    if let Some(loc) = &player.loc {
+        for (axis, value) in [("x", loc.x), ("y", loc.y), ("z", loc.z)] {
+            if !value.is_finite() {
+                problems.push(lines.problem(field(&format!("loc.{}", axis)), format!("{} is not a coordinate", value)));
+            }
+        }
+        if [loc.x, loc.y, loc.z].iter().all(|value| value.is_finite())
+            && let Err(e) = config.arena.check(&Location::from(loc))
+        {
+            problems.push(lines.problem(field("loc"), e));
+        }
+    }
+    if let Err(e) = config.items.equip(&player.equipment) {
+        let slot = if e.kind == "consumable" { "consumables" } else { e.kind };
+        problems.push(lines.problem(field(&format!("equipment.{}", slot)), e));
+    }
+    if let Err(e) = abilities::learn(&config.abilities, &player.abilities) {
+        problems.push(lines.problem(field("abilities"), e));
+    }
+    problems
+}
+
+/// The attributes the player's config gives, with the range each has to lie in.
+fn attributes(player: &PlayerConfig) -> Vec<(&'static str, i32, RangeInclusive<i32>)> {
+    [
+        ("attack", player.attack, ATTRIBUTE_RANGE),
+        ("defense", player.defense, ATTRIBUTE_RANGE),
+        ("armor", player.armor, VITAL_RANGE),
+        ("power", player.power, ATTRIBUTE_RANGE),
+        ("speed", player.speed, VITAL_RANGE),
+        ("range", player.range, ATTRIBUTE_RANGE),
+        ("regen", player.regen, ATTRIBUTE_RANGE),
+        ("morale", player.morale, ATTRIBUTE_RANGE),
+    ]
+    .into_iter()
+    .filter_map(|(name, value, range)| Some((name, value?, range)))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::parse_simulation_config;
+
+    #[test]
+    fn test_line_index() {
+        let lines = LineIndex::new(
+            "rules:\n  attack: 1d20\nplayers:\n- name: A\n  loc: { x: 1, y: 2 }\n-\n  name: B\n  loc:\n    x: 3\n",
+        );
+        assert_eq!(lines.line("rules.attack"), Some(2));
+        assert_eq!(lines.line("players[0].name"), Some(4));
+        assert_eq!(lines.line("players[0].loc.x"), Some(5));
+        assert_eq!(lines.line("players[1]"), Some(6));
+        assert_eq!(lines.line("players[1].loc.x"), Some(9));
+        assert_eq!(lines.line("seed"), None);
+    }
+
+    #[test]
+    fn test_every_problem_is_reported() {
+        let config = "
+version: 3
+players:
+  - name: Fred
+    attack: 12
+    defense: 12
+    armor: 0
+    power: 12
+    speed: 12
+    range: 12
+    loc: { x: 5, y: 6, z: 0 }
+  - name: Fred
+    attak: 12
+    attack: 12
+    defense: 12
+    armor: 12
+    power: 12
+    speed: 12
+    range: 12
+    abilities: [fireball]
+    loc:
+      x: .nan
+      y: 6
+      z: 0
+";
+        let problems: Vec<String> = parse_simulation_config(config)
+            .unwrap_err()
+            .iter()
+            .map(|problem| problem.to_string())
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "13: players[1].attak: unknown field",
+                "2: version: 3 is newer than the supported version 2",
+                "7: players[0].armor: 0 is out of range 1..=100",
+                "12: players[1].name: Fred is already the name of players[0]",
+                "22: players[1].loc.x: NaN is not a coordinate",
+                "20: players[1].abilities: no ability named 'fireball' in the abilities section",
+            ]
+        );
+        assert!(parse_simulation_config("players: []").is_err());
+
+        let config = "
+templates:
+  knight:
+    template: squire
+  squire:
+    template: knight
+players:
+  - template: archer
+  - name: knight 2
+  - template: knight
+    count: 2
+";
+        let problems: Vec<String> = parse_simulation_config(config)
+            .unwrap_err()
+            .iter()
+            .map(|problem| problem.to_string())
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "4: templates.knight.template: template 'squire' ends up inheriting from itself",
+                "6: templates.squire.template: template 'knight' ends up inheriting from itself",
+                "8: players[0].template: no template named 'archer'",
+                "10: players[2].name: knight 2 is already the name of players[1]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overlapping_starts_are_reported() {
+        let config = "
+arena:
+  width: 30
+  height: 30
+  walls:
+    - { x: 0, y: 2, width: 4, height: 1 }
+    - { x: 2, y: 0, width: 1, height: 4 }
+players:
+  - name: A
+    loc: { x: 20, y: 20, z: 0 }
+  - name: B
+    loc: { x: 20.5, y: 20, z: 0 }
+  - count: 3
+    loc: { x: 1, y: 1, z: 0 }
+";
+        let problems: Vec<String> = parse_simulation_config(config)
+            .unwrap_err()
+            .iter()
+            .map(|problem| problem.to_string())
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "12: players[1].loc: starts on top of players[0]",
+                "14: players[2].loc: there is no room to spread the squad out here",
+            ]
+        );
+
+        let config = "
+arena:
+  cover:
+    - { x: 1, y: 1, width: 2, height: 2, defence: 3 }
+  elevation:
+    - { x: 5, y: 5, width: 2, height: 2, level: 2, levle: 3 }
+abilities:
+  volley:
+    kind: volley
+    shots: 2
+    cooldown: 3
+    rnage: 4
+players:
+  - abilities: [volley]
+";
+        let problems: Vec<String> = parse_simulation_config(config)
+            .unwrap_err()
+            .iter()
+            .map(|problem| problem.to_string())
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "4: arena.cover[0].defence: unknown field",
+                "6: arena.elevation[0].levle: unknown field",
+                "12: abilities.volley.rnage: unknown field",
+            ]
+        );
+    }
+}